@@ -118,7 +118,7 @@ pub fn escape_rulename(x: &str) -> String {
 }
 
 fn format_char(x: u32) -> String {
-    if x <= u32::from(u8::max_value()) {
+    if x <= u32::from(u8::MAX) {
         let x: u8 = x as u8;
         if x.is_ascii_graphic() {
             let x: char = x as char;
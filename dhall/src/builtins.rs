@@ -18,6 +18,10 @@ pub enum Builtin {
     Integer,
     Double,
     Text,
+    Date,
+    Time,
+    TimeZone,
+    Bytes,
     List,
     Optional,
     OptionalNone,
@@ -43,6 +47,10 @@ pub enum Builtin {
     ListReverse,
     TextShow,
     TextReplace,
+    DateShow,
+    TimeShow,
+    TimeZoneShow,
+    BytesShow,
 }
 
 impl Builtin {
@@ -54,6 +62,10 @@ impl Builtin {
             "Integer" => Some(Integer),
             "Double" => Some(Double),
             "Text" => Some(Text),
+            "Date" => Some(Date),
+            "Time" => Some(Time),
+            "TimeZone" => Some(TimeZone),
+            "Bytes" => Some(Bytes),
             "List" => Some(List),
             "Optional" => Some(Optional),
             "None" => Some(OptionalNone),
@@ -79,6 +91,10 @@ impl Builtin {
             "List/reverse" => Some(ListReverse),
             "Text/show" => Some(TextShow),
             "Text/replace" => Some(TextReplace),
+            "Date/show" => Some(DateShow),
+            "Time/show" => Some(TimeShow),
+            "TimeZone/show" => Some(TimeZoneShow),
+            "Bytes/show" => Some(BytesShow),
             _ => None,
         }
     }
@@ -128,6 +144,10 @@ macro_rules! make_type {
     (Integer) => { rc(ExprKind::Builtin(Builtin::Integer)) };
     (Double) => { rc(ExprKind::Builtin(Builtin::Double)) };
     (Text) => { rc(ExprKind::Builtin(Builtin::Text)) };
+    (Date) => { rc(ExprKind::Builtin(Builtin::Date)) };
+    (Time) => { rc(ExprKind::Builtin(Builtin::Time)) };
+    (TimeZone) => { rc(ExprKind::Builtin(Builtin::TimeZone)) };
+    (Bytes) => { rc(ExprKind::Builtin(Builtin::Bytes)) };
     ($var:ident) => {
         rc(ExprKind::Var(V(stringify!($var).into(), 0)))
     };
@@ -179,7 +199,8 @@ macro_rules! make_type {
 pub fn type_of_builtin<'cx>(cx: Ctxt<'cx>, b: Builtin) -> Hir<'cx> {
     use Builtin::*;
     let expr = match b {
-        Bool | Natural | Integer | Double | Text => make_type!(Type),
+        Bool | Natural | Integer | Double | Text | Date | Time | TimeZone
+        | Bytes => make_type!(Type),
         List | Optional => make_type!(
             Type -> Type
         ),
@@ -211,6 +232,10 @@ pub fn type_of_builtin<'cx>(cx: Ctxt<'cx>, b: Builtin) -> Hir<'cx> {
         IntegerClamp => make_type!(Integer -> Natural),
 
         DoubleShow => make_type!(Double -> Text),
+        DateShow => make_type!(Date -> Text),
+        TimeShow => make_type!(Time -> Text),
+        TimeZoneShow => make_type!(TimeZone -> Text),
+        BytesShow => make_type!(Bytes -> Text),
         TextShow => make_type!(Text -> Text),
         TextReplace => make_type!(
             forall (needle: Text) ->
@@ -254,7 +279,7 @@ pub fn type_of_builtin<'cx>(cx: Ctxt<'cx>, b: Builtin) -> Hir<'cx> {
     Parsed::from_expr_without_imports(expr)
         .resolve(cx)
         .unwrap()
-        .0
+        .hir
 }
 
 // Ad-hoc macro to help construct closures
@@ -315,7 +340,9 @@ fn apply_builtin<'cx>(
 ) -> NirKind<'cx> {
     let cx = env.cx();
     use NirKind::*;
-    use NumKind::{Bool, Double, Integer, Natural};
+    use NumKind::{
+        Bool, Bytes, Date, Double, Integer, Natural, Time, TimeZone,
+    };
 
     // Small helper enum
     enum Ret<'cx> {
@@ -338,34 +365,41 @@ fn apply_builtin<'cx>(
         | (Builtin::Natural, [])
         | (Builtin::Integer, [])
         | (Builtin::Double, [])
-        | (Builtin::Text, []) => Ret::NirKind(BuiltinType(b)),
+        | (Builtin::Text, [])
+        | (Builtin::Date, [])
+        | (Builtin::Time, [])
+        | (Builtin::TimeZone, [])
+        | (Builtin::Bytes, []) => Ret::NirKind(BuiltinType(b)),
         (Builtin::Optional, [t]) => Ret::NirKind(OptionalType(t.clone())),
         (Builtin::List, [t]) => Ret::NirKind(ListType(t.clone())),
 
         (Builtin::OptionalNone, [t]) => {
             Ret::NirKind(EmptyOptionalLit(t.clone()))
         }
-        (Builtin::NaturalIsZero, [n]) => match &*n.kind() {
+        (Builtin::NaturalIsZero, [n]) => match n.kind() {
             Num(Natural(n)) => Ret::NirKind(Num(Bool(*n == 0))),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::NaturalEven, [n]) => match &*n.kind() {
+        (Builtin::NaturalEven, [n]) => match n.kind() {
             Num(Natural(n)) => Ret::NirKind(Num(Bool(*n % 2 == 0))),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::NaturalOdd, [n]) => match &*n.kind() {
+        (Builtin::NaturalOdd, [n]) => match n.kind() {
             Num(Natural(n)) => Ret::NirKind(Num(Bool(*n % 2 != 0))),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::NaturalToInteger, [n]) => match &*n.kind() {
-            Num(Natural(n)) => Ret::NirKind(Num(Integer(*n as i64))),
+        (Builtin::NaturalToInteger, [n]) => match n.kind() {
+            Num(Natural(n)) => match (*n).try_into() {
+                Ok(n) => Ret::NirKind(Num(Integer(n))),
+                Err(_) => Ret::DoneAsIs,
+            },
             _ => Ret::DoneAsIs,
         },
-        (Builtin::NaturalShow, [n]) => match &*n.kind() {
+        (Builtin::NaturalShow, [n]) => match n.kind() {
             Num(Natural(n)) => Ret::Nir(Nir::from_text(n)),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::NaturalSubtract, [a, b]) => match (&*a.kind(), &*b.kind()) {
+        (Builtin::NaturalSubtract, [a, b]) => match (a.kind(), b.kind()) {
             (Num(Natural(a)), Num(Natural(b))) => {
                 Ret::NirKind(Num(Natural(if b > a { b - a } else { 0 })))
             }
@@ -374,7 +408,7 @@ fn apply_builtin<'cx>(
             _ if a == b => Ret::NirKind(Num(Natural(0))),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::IntegerShow, [n]) => match &*n.kind() {
+        (Builtin::IntegerShow, [n]) => match n.kind() {
             Num(Integer(n)) => {
                 let s = if *n < 0 {
                     n.to_string()
@@ -385,27 +419,46 @@ fn apply_builtin<'cx>(
             }
             _ => Ret::DoneAsIs,
         },
-        (Builtin::IntegerToDouble, [n]) => match &*n.kind() {
+        (Builtin::IntegerToDouble, [n]) => match n.kind() {
             Num(Integer(n)) => {
                 Ret::NirKind(Num(Double(NaiveDouble::from(*n as f64))))
             }
             _ => Ret::DoneAsIs,
         },
-        (Builtin::IntegerNegate, [n]) => match &*n.kind() {
-            Num(Integer(n)) => Ret::NirKind(Num(Integer(-n))),
+        (Builtin::IntegerNegate, [n]) => match n.kind() {
+            Num(Integer(n)) => match n.checked_neg() {
+                Some(n) => Ret::NirKind(Num(Integer(n))),
+                None => Ret::DoneAsIs,
+            },
             _ => Ret::DoneAsIs,
         },
-        (Builtin::IntegerClamp, [n]) => match &*n.kind() {
+        (Builtin::IntegerClamp, [n]) => match n.kind() {
             Num(Integer(n)) => {
                 Ret::NirKind(Num(Natural((*n).try_into().unwrap_or(0))))
             }
             _ => Ret::DoneAsIs,
         },
-        (Builtin::DoubleShow, [n]) => match &*n.kind() {
+        (Builtin::DoubleShow, [n]) => match n.kind() {
             Num(Double(n)) => Ret::Nir(Nir::from_text(n)),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::TextShow, [v]) => match &*v.kind() {
+        (Builtin::DateShow, [d]) => match d.kind() {
+            Num(Date(d)) => Ret::Nir(Nir::from_text(d)),
+            _ => Ret::DoneAsIs,
+        },
+        (Builtin::TimeShow, [t]) => match t.kind() {
+            Num(Time(t)) => Ret::Nir(Nir::from_text(t)),
+            _ => Ret::DoneAsIs,
+        },
+        (Builtin::TimeZoneShow, [tz]) => match tz.kind() {
+            Num(TimeZone(tz)) => Ret::Nir(Nir::from_text(tz)),
+            _ => Ret::DoneAsIs,
+        },
+        (Builtin::BytesShow, [b]) => match b.kind() {
+            Num(b @ Bytes(_)) => Ret::Nir(Nir::from_text(b)),
+            _ => Ret::DoneAsIs,
+        },
+        (Builtin::TextShow, [v]) => match v.kind() {
             TextLit(tlit) => {
                 if let Some(s) = tlit.as_text() {
                     // Printing InterpolatedText takes care of all the escaping
@@ -422,7 +475,7 @@ fn apply_builtin<'cx>(
         (Builtin::TextReplace, [needle, replacement, haystack]) => {
             // Helper to match a Nir as a text literal
             fn nir_to_string(n: &Nir) -> Option<String> {
-                match &*n.kind() {
+                match n.kind() {
                     TextLit(n_lit) => n_lit.as_text(),
                     _ => None,
                 }
@@ -451,9 +504,10 @@ fn apply_builtin<'cx>(
                             );
 
                             Ret::Nir(Nir::from_kind(NirKind::TextLit(
-                                nze::nir::TextLit::new(
-                                    parts.intersperse(replacement),
-                                ),
+                                nze::nir::TextLit::new(Itertools::intersperse(
+                                    parts,
+                                    replacement,
+                                )),
                             )))
                         }
                     } else {
@@ -463,26 +517,26 @@ fn apply_builtin<'cx>(
                 _ => Ret::DoneAsIs,
             }
         }
-        (Builtin::ListLength, [_, l]) => match &*l.kind() {
+        (Builtin::ListLength, [_, l]) => match l.kind() {
             EmptyListLit(_) => Ret::NirKind(Num(Natural(0))),
             NEListLit(xs) => Ret::NirKind(Num(Natural(xs.len() as u64))),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::ListHead, [_, l]) => match &*l.kind() {
+        (Builtin::ListHead, [_, l]) => match l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyOptionalLit(n.clone())),
             NEListLit(xs) => {
                 Ret::NirKind(NEOptionalLit(xs.iter().next().unwrap().clone()))
             }
             _ => Ret::DoneAsIs,
         },
-        (Builtin::ListLast, [_, l]) => match &*l.kind() {
+        (Builtin::ListLast, [_, l]) => match l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyOptionalLit(n.clone())),
             NEListLit(xs) => Ret::NirKind(NEOptionalLit(
-                xs.iter().rev().next().unwrap().clone(),
+                xs.iter().next_back().unwrap().clone(),
             )),
             _ => Ret::DoneAsIs,
         },
-        (Builtin::ListReverse, [_, l]) => match &*l.kind() {
+        (Builtin::ListReverse, [_, l]) => match l.kind() {
             EmptyListLit(n) => Ret::NirKind(EmptyListLit(n.clone())),
             NEListLit(xs) => {
                 Ret::NirKind(NEListLit(xs.iter().rev().cloned().collect()))
@@ -541,7 +595,7 @@ fn apply_builtin<'cx>(
                     .app(EmptyListLit(t.clone()).into_nir()),
             )
         }
-        (Builtin::ListFold, [_, l, _, cons, nil]) => match &*l.kind() {
+        (Builtin::ListFold, [_, l, _, cons, nil]) => match l.kind() {
             EmptyListLit(_) => Ret::Nir(nil.clone()),
             NEListLit(xs) => {
                 let mut v = nil.clone();
@@ -561,7 +615,7 @@ fn apply_builtin<'cx>(
                 .app(Num(Natural(0)).into_nir()),
         ),
 
-        (Builtin::NaturalFold, [n, t, succ, zero]) => match &*n.kind() {
+        (Builtin::NaturalFold, [n, t, succ, zero]) => match n.kind() {
             Num(Natural(0)) => Ret::Nir(zero.clone()),
             Num(Natural(n)) => {
                 let fold = Nir::from_builtin(cx, Builtin::NaturalFold)
@@ -598,6 +652,10 @@ impl std::fmt::Display for Builtin {
             Integer => "Integer",
             Double => "Double",
             Text => "Text",
+            Date => "Date",
+            Time => "Time",
+            TimeZone => "TimeZone",
+            Bytes => "Bytes",
             List => "List",
             Optional => "Optional",
             OptionalNone => "None",
@@ -623,6 +681,10 @@ impl std::fmt::Display for Builtin {
             ListReverse => "List/reverse",
             TextShow => "Text/show",
             TextReplace => "Text/replace",
+            DateShow => "Date/show",
+            TimeShow => "Time/show",
+            TimeZoneShow => "TimeZone/show",
+            BytesShow => "Bytes/show",
         })
     }
 }
@@ -15,7 +15,8 @@ use crate::Typed;
 pub struct CtxtS<'cx> {
     imports: FrozenVec<Box<StoredImport<'cx>>>,
     import_alternatives: FrozenVec<Box<StoredImportAlternative<'cx>>>,
-    import_results: FrozenVec<Box<StoredImportResult<'cx>>>,
+    import_results:
+        FrozenVec<Box<(StoredImportResult<'cx>, ResultImports<'cx>)>>,
 }
 
 /// Context for the dhall compiler. Stores various global maps.
@@ -65,12 +66,22 @@ pub struct ImportId<'cx>(usize, PhantomData<&'cx ()>);
 pub struct StoredImport<'cx> {
     cx: Ctxt<'cx>,
     pub base_location: ImportLocation,
-    pub import: Import,
+    pub import: Import<'cx>,
     pub span: Span,
+    location: OnceCell<ImportLocation>,
     result: OnceCell<ImportResultId<'cx>>,
 }
 
 impl<'cx> StoredImport<'cx> {
+    /// Get the location this import points to. Returns `None` if resolving this import wasn't
+    /// attempted, or if the location couldn't be computed.
+    pub fn get_location(&self) -> Option<&ImportLocation> {
+        self.location.get()
+    }
+    /// Store the location this import points to.
+    pub fn set_location(&self, location: ImportLocation) {
+        let _ = self.location.set(location);
+    }
     /// Get the id of the result of fetching this import. Returns `None` if the result has not yet
     /// been fetched.
     pub fn get_resultid(&self) -> Option<ImportResultId<'cx>> {
@@ -107,7 +118,7 @@ impl<'cx> Ctxt<'cx> {
     pub fn push_import(
         self,
         base_location: ImportLocation,
-        import: Import,
+        import: Import<'cx>,
         span: Span,
     ) -> ImportId<'cx> {
         let stored = StoredImport {
@@ -115,6 +126,7 @@ impl<'cx> Ctxt<'cx> {
             base_location,
             import,
             span,
+            location: OnceCell::new(),
             result: OnceCell::new(),
         };
         let id = self.0.imports.len();
@@ -139,6 +151,8 @@ pub struct ImportAlternativeId<'cx>(usize, PhantomData<&'cx ()>);
 pub struct StoredImportAlternative<'cx> {
     pub left_imports: Box<[ImportNode<'cx>]>,
     pub right_imports: Box<[ImportNode<'cx>]>,
+    pub left_span: Span,
+    pub right_span: Span,
     /// `true` for left, `false` for right.
     selected: OnceCell<bool>,
 }
@@ -163,10 +177,14 @@ impl<'cx> Ctxt<'cx> {
         self,
         left_imports: Box<[ImportNode<'cx>]>,
         right_imports: Box<[ImportNode<'cx>]>,
+        left_span: Span,
+        right_span: Span,
     ) -> ImportAlternativeId<'cx> {
         let stored = StoredImportAlternative {
             left_imports,
             right_imports,
+            left_span,
+            right_span,
             selected: OnceCell::new(),
         };
         let id = self.0.import_alternatives.len();
@@ -192,20 +210,41 @@ pub struct ImportResultId<'cx>(usize, PhantomData<&'cx ()>);
 
 type StoredImportResult<'cx> = Typed<'cx>;
 
+/// The imports of the file a result was read from, or `None` if it wasn't read from its location,
+/// e.g. because it was found in a cache.
+pub type ResultImports<'cx> = Option<Box<[ImportNode<'cx>]>>;
+
 impl<'cx> Ctxt<'cx> {
     /// Store the result of fetching an import.
     pub fn push_import_result(
         self,
         res: StoredImportResult<'cx>,
+    ) -> ImportResultId<'cx> {
+        self.push_import_result_with_imports(res, None)
+    }
+    /// Store the result of fetching an import, along with the imports of the file it was read
+    /// from.
+    pub fn push_import_result_with_imports(
+        self,
+        res: StoredImportResult<'cx>,
+        imports: ResultImports<'cx>,
     ) -> ImportResultId<'cx> {
         let id = self.0.import_results.len();
-        self.0.import_results.push(Box::new(res));
+        self.0.import_results.push(Box::new((res, imports)));
         ImportResultId(id, PhantomData)
     }
+    /// Get the imports of the file a result was read from. Returns `None` if the result wasn't
+    /// read from its location.
+    pub fn get_result_imports(
+        self,
+        id: ImportResultId<'cx>,
+    ) -> Option<&'cx [ImportNode<'cx>]> {
+        self.0.import_results[id.0].1.as_deref()
+    }
 }
 impl<'cx> Index<ImportResultId<'cx>> for CtxtS<'cx> {
     type Output = StoredImportResult<'cx>;
     fn index(&self, id: ImportResultId<'cx>) -> &StoredImportResult<'cx> {
-        &self.import_results[id.0]
+        &self.import_results[id.0].0
     }
 }
@@ -63,6 +63,13 @@ impl ErrorBuilder {
         }
     }
 
+    /// Like `new`, for a message that gives context to another error.
+    pub fn new_note(message: impl ToString) -> Self {
+        let mut builder = ErrorBuilder::new(message);
+        builder.title.annotation_type = AnnotationType::Note;
+        builder
+    }
+
     pub fn span_annot(
         &mut self,
         span: Span,
@@ -115,7 +122,7 @@ impl ErrorBuilder {
     }
 
     // TODO: handle multiple files
-    #[allow(clippy::drop_ref)]
+    #[allow(dropping_references)]
     pub fn format(&mut self) -> String {
         if self.consumed {
             panic!("tried to format the same ErrorBuilder twice")
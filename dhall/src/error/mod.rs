@@ -1,7 +1,8 @@
 use std::io::Error as IOError;
 
-use crate::semantics::resolve::{CyclesStack, ImportLocation};
-use crate::syntax::{Import, ParseError};
+use crate::semantics::resolve::{CyclesStack, ImportFrame, ImportLocation};
+use crate::standard::{Feature, StandardVersion};
+use crate::syntax::{Import, ParseError, Span};
 
 mod builder;
 pub use builder::*;
@@ -10,7 +11,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub struct Error {
-    kind: ErrorKind,
+    // Boxed so that `Result`s stay small.
+    kind: Box<ErrorKind>,
 }
 
 #[derive(Debug)]
@@ -23,6 +25,8 @@ pub enum ErrorKind {
     Resolve(ImportError),
     Typecheck(TypeError),
     Cache(CacheError),
+    Standard(StandardError),
+    Overflow(OverflowError),
 }
 
 #[derive(Debug)]
@@ -30,11 +34,80 @@ pub enum ImportError {
     Missing,
     MissingEnvVar,
     SanityCheck,
+    /// A remote file imported from a different origin did not allow that origin.
+    CorsCheck {
+        origin: String,
+        allow_origin: Option<String>,
+    },
     UnexpectedImport(Import<()>),
+    InvalidHeaders,
+    /// Failed to connect to the server or to complete the request.
+    Network {
+        url: String,
+        error: String,
+    },
+    /// The server answered with a non-success status code.
+    HttpStatus {
+        url: String,
+        status: u16,
+    },
+    /// Failed to read or decode the response body.
+    ResponseDecode {
+        url: String,
+        error: String,
+    },
+    /// This build of dhall, or the fetcher in use, can't fetch this url.
+    RemoteImportsDisabled(String),
+    /// Tried to download this url in offline mode.
+    NetworkDisabled(String),
     ImportCycle(CyclesStack, ImportLocation),
+    /// Resolving the last import of the stack failed with this error. The stack lists the
+    /// imports that led there, outermost first.
+    InImport(CyclesStack, Box<Error>),
+    /// Every alternative of a `?` failed, in order.
+    AlternativesFailed(Vec<FailedAlternative>),
+    /// The import policy doesn't allow this import.
+    Forbidden(ImportLocation),
+    /// The lockfile has no entry for this url, and is only checked.
+    NotLocked(String),
+    /// The lockfile couldn't be parsed.
+    InvalidLockfile(String),
     Url(url::ParseError),
 }
 
+/// A side of a `?` that failed to resolve.
+pub struct FailedAlternative {
+    pub span: Span,
+    pub error: Error,
+}
+
+/// Leaves out the span, whose `Debug` output includes the whole source file.
+impl std::fmt::Debug for FailedAlternative {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FailedAlternative")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for FailedAlternative {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.error.kind() {
+            // These already point to the failed import.
+            ErrorKind::Resolve(ImportError::InImport(..)) => {
+                write!(f, "{}", self.error)
+            }
+            _ => write!(
+                f,
+                "{}",
+                ErrorBuilder::new(&self.error)
+                    .span_err(self.span.clone(), "in this alternative")
+                    .format()
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DecodeError {
     CBORError(serde_cbor::error::Error),
@@ -61,17 +134,61 @@ pub enum TypeMessage {
 #[derive(Debug)]
 pub enum CacheError {
     MissingConfiguration,
-    InitialisationError { cause: IOError },
+    InitialisationError {
+        cause: IOError,
+    },
     CacheHashInvalid,
+    /// Tried to modify a read-only cache.
+    ReadOnly,
+}
+
+/// The expression uses a feature that was added after the required version of the standard.
+#[derive(Debug)]
+pub struct StandardError {
+    pub feature: Feature,
+    /// The required version.
+    pub version: StandardVersion,
+    pub span: Span,
+}
+
+/// A `Natural` or `Integer` does not fit in 64 bits.
+#[derive(Debug)]
+pub struct OverflowError {
+    /// The number, or the operation whose result is too large.
+    pub expr: String,
 }
 
 impl Error {
     pub fn new(kind: ErrorKind) -> Self {
-        Error { kind }
+        Error {
+            kind: Box::new(kind),
+        }
     }
     pub fn kind(&self) -> &ErrorKind {
         &self.kind
     }
+    /// If every alternative of a `?` failed, get their errors.
+    pub(crate) fn into_alternatives_failed(
+        self,
+    ) -> std::result::Result<Vec<FailedAlternative>, Self> {
+        match *self.kind {
+            ErrorKind::Resolve(ImportError::AlternativesFailed(failed)) => {
+                Ok(failed)
+            }
+            kind => Err(Error::new(kind)),
+        }
+    }
+    /// Record that this error happened while resolving the import `frame`.
+    pub(crate) fn in_import(self, frame: ImportFrame) -> Self {
+        let (stack, error) = match *self.kind {
+            ErrorKind::Resolve(ImportError::InImport(mut stack, error)) => {
+                stack.insert(0, frame);
+                (stack, error)
+            }
+            kind => (vec![frame], Box::new(Error::new(kind))),
+        };
+        ImportError::InImport(stack, error).into()
+    }
 }
 
 impl TypeError {
@@ -105,19 +222,119 @@ impl std::error::Error for EncodeError {}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &self.kind {
+        match &*self.kind {
             ErrorKind::IO(err) => write!(f, "{}", err),
             ErrorKind::Parse(err) => write!(f, "{}", err),
             ErrorKind::Decode(err) => write!(f, "{:?}", err),
             ErrorKind::Encode(err) => write!(f, "{:?}", err),
+            ErrorKind::Resolve(ImportError::InImport(stack, err)) => {
+                // Like the reference implementation, show the chain of imports that led to the
+                // error.
+                for (i, frame) in stack.iter().enumerate() {
+                    writeln!(f, "{}↳ {}", "  ".repeat(i), frame.location)?;
+                }
+                writeln!(f)?;
+                // Each span is in the file of the previous frame, so each gets its own snippet.
+                let mut frames = stack.iter().rev();
+                let innermost = frames.next().unwrap();
+                write!(
+                    f,
+                    "{}",
+                    ErrorBuilder::new(err)
+                        .span_err(innermost.span.clone(), "imported here")
+                        .format()
+                )?;
+                for frame in frames {
+                    let note = ErrorBuilder::new_note(format!(
+                        "while importing {}",
+                        frame.location
+                    ))
+                    .span_help(frame.span.clone(), "imported here")
+                    .format();
+                    write!(f, "\n{}", note)?;
+                }
+                Ok(())
+            }
+            ErrorKind::Resolve(ImportError::AlternativesFailed(failed)) => {
+                write!(f, "every alternative of `?` failed")?;
+                for alternative in failed {
+                    write!(f, "\n\n{}", alternative)?;
+                }
+                Ok(())
+            }
+            ErrorKind::Resolve(ImportError::ImportCycle(_, location)) => {
+                write!(f, "ImportCycle: {} is already being imported", location)
+            }
+            ErrorKind::Resolve(ImportError::Network { url, error }) => {
+                write!(f, "failed to download {}: {}", url, error)
+            }
+            ErrorKind::Resolve(ImportError::HttpStatus { url, status }) => {
+                write!(f, "failed to download {}: HTTP status {}", url, status)
+            }
+            ErrorKind::Resolve(ImportError::ResponseDecode { url, error }) => {
+                write!(f, "failed to read the response from {}: {}", url, error)
+            }
+            ErrorKind::Resolve(ImportError::RemoteImportsDisabled(url)) => {
+                write!(f, "remote imports are disabled: cannot fetch {}", url)
+            }
+            ErrorKind::Resolve(ImportError::NetworkDisabled(url)) => write!(
+                f,
+                "network access is disabled (offline mode): cannot fetch {}",
+                url
+            ),
+            ErrorKind::Resolve(ImportError::Forbidden(location)) => {
+                write!(f, "the import policy forbids importing {}", location)
+            }
+            ErrorKind::Resolve(ImportError::NotLocked(url)) => write!(
+                f,
+                "{} is not in the lockfile; update the lockfile to add it",
+                url
+            ),
+            ErrorKind::Resolve(ImportError::InvalidLockfile(msg)) => {
+                write!(f, "invalid lockfile: {}", msg)
+            }
             ErrorKind::Resolve(err) => write!(f, "{:?}", err),
             ErrorKind::Typecheck(err) => write!(f, "{}", err),
             ErrorKind::Cache(err) => write!(f, "{:?}", err),
+            ErrorKind::Standard(err) => write!(f, "{}", err),
+            ErrorKind::Overflow(err) => write!(f, "{}", err),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+impl std::fmt::Display for StandardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = format!(
+            "{} (added in version {} of the standard) cannot be used with \
+             version {}",
+            self.feature,
+            self.feature.since(),
+            self.version
+        );
+        write!(
+            f,
+            "{}",
+            ErrorBuilder::new(message)
+                .span_err(
+                    self.span.clone(),
+                    format!("not in version {}", self.version),
+                )
+                .format()
+        )
+    }
+}
+impl std::fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` overflows: `Natural` and `Integer` are limited to 64 bits",
+            self.expr
+        )
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Error {
         Error::new(kind)
@@ -14,20 +14,26 @@ pub mod ctxt;
 pub mod error;
 pub mod operations;
 pub mod semantics;
+pub mod standard;
 pub mod syntax;
 pub mod utils;
 
 use std::path::Path;
 use url::Url;
 
-use crate::error::{Error, TypeError};
+use crate::error::{Error, ErrorKind, OverflowError, TypeError};
+use crate::operations::find_overflow;
 use crate::semantics::parse;
 use crate::semantics::resolve;
-use crate::semantics::resolve::ImportLocation;
+use crate::semantics::resolve::{
+    ImportFetcher, ImportGraph, ImportLocation, ImportNode,
+};
 use crate::semantics::{typecheck, typecheck_with, Hir, Nir, Tir, Type};
-use crate::syntax::Expr;
+use crate::standard::StandardVersion;
+use crate::syntax::{Expr, Hash};
 
 pub use ctxt::*;
+pub use standard::STANDARD_VERSION;
 
 #[derive(Debug, Clone)]
 pub struct Parsed(Expr, ImportLocation);
@@ -36,7 +42,13 @@ pub struct Parsed(Expr, ImportLocation);
 ///
 /// Invariant: there must be no `Import` nodes or `ImportAlt` operations left.
 #[derive(Debug, Clone)]
-pub struct Resolved<'cx>(Hir<'cx>);
+pub struct Resolved<'cx> {
+    hir: Hir<'cx>,
+    /// The location the expression was read from.
+    location: ImportLocation,
+    /// The imports of the expression, and which side of each alternative was selected.
+    imports: Box<[ImportNode<'cx>]>,
+}
 
 /// A typed expression
 #[derive(Debug, Clone)]
@@ -67,6 +79,14 @@ impl Parsed {
     pub fn parse_file(f: &Path) -> Result<Parsed, Error> {
         parse::parse_file(f)
     }
+    /// Parse a file read with `fetcher`, e.g. from `VirtualFiles`. Resolve it with the same
+    /// fetcher to read its relative imports from the same place.
+    pub fn parse_file_with(
+        f: &Path,
+        fetcher: &impl ImportFetcher,
+    ) -> Result<Parsed, Error> {
+        parse::parse_file_with(f, fetcher)
+    }
     pub fn parse_remote(url: Url) -> Result<Parsed, Error> {
         parse::parse_remote(url)
     }
@@ -84,6 +104,14 @@ impl Parsed {
     pub fn resolve<'cx>(self, cx: Ctxt<'cx>) -> Result<Resolved<'cx>, Error> {
         resolve::resolve(cx, self)
     }
+    /// Resolve imports, using `fetcher` to get the contents of each import.
+    pub fn resolve_with<'cx>(
+        self,
+        cx: Ctxt<'cx>,
+        fetcher: impl ImportFetcher + 'static,
+    ) -> Result<Resolved<'cx>, Error> {
+        resolve::resolve_with(cx, self, fetcher)
+    }
     pub fn skip_resolve<'cx>(
         self,
         cx: Ctxt<'cx>,
@@ -91,6 +119,15 @@ impl Parsed {
         resolve::skip_resolve(cx, self)
     }
 
+    /// Errors if this expression uses a feature that was added after `version` of the standard.
+    /// Imports are not checked; use `ImportEnv::with_standard_version` for that.
+    pub fn check_standard_version(
+        &self,
+        version: StandardVersion,
+    ) -> Result<(), Error> {
+        standard::check_version(&self.0, version)
+    }
+
     /// Converts a value back to the corresponding AST expression.
     pub fn to_expr(&self) -> Expr {
         self.0.clone()
@@ -99,18 +136,22 @@ impl Parsed {
 
 impl<'cx> Resolved<'cx> {
     pub fn typecheck(&self, cx: Ctxt<'cx>) -> Result<Typed<'cx>, TypeError> {
-        Ok(Typed::from_tir(typecheck(cx, &self.0)?))
+        Ok(Typed::from_tir(typecheck(cx, &self.hir)?))
     }
     pub fn typecheck_with(
         self,
         cx: Ctxt<'cx>,
         ty: &Hir<'cx>,
     ) -> Result<Typed<'cx>, TypeError> {
-        Ok(Typed::from_tir(typecheck_with(cx, &self.0, ty)?))
+        Ok(Typed::from_tir(typecheck_with(cx, &self.hir, ty)?))
     }
     /// Converts a value back to the corresponding AST expression.
     pub fn to_expr(&self, cx: Ctxt<'cx>) -> Expr {
-        self.0.to_expr_noopts(cx)
+        self.hir.to_expr_noopts(cx)
+    }
+    /// The imports that were resolved to get this expression. See `ImportGraph`.
+    pub fn import_graph(&self, cx: Ctxt<'cx>) -> ImportGraph {
+        ImportGraph::new(cx, &self.location, &self.imports)
     }
 }
 
@@ -121,14 +162,36 @@ impl<'cx> Typed<'cx> {
             ty: tir.ty().clone(),
         }
     }
-    /// Reduce an expression to its normal form, performing beta reduction
+    /// Reduce an expression to its normal form, performing beta reduction. Operations whose
+    /// `Natural` or `Integer` result doesn't fit in 64 bits are left unevaluated; see
+    /// `normalize_checked`.
     pub fn normalize(&self, cx: Ctxt<'cx>) -> Normalized<'cx> {
         Normalized(self.hir.eval_closed_expr(cx))
     }
+    /// Like `normalize`, but fails if a `Natural` or `Integer` result doesn't fit in 64 bits.
+    /// This has to go through the whole normal form.
+    pub fn normalize_checked(
+        &self,
+        cx: Ctxt<'cx>,
+    ) -> Result<Normalized<'cx>, Error> {
+        let normalized = self.normalize(cx);
+        match find_overflow(&normalized.to_expr(cx)) {
+            Some(expr) => Err(ErrorKind::Overflow(OverflowError {
+                expr: expr.to_string(),
+            })
+            .into()),
+            None => Ok(normalized),
+        }
+    }
 
-    /// Converts a value back to the corresponding AST expression.
-    fn to_expr(&self, cx: Ctxt<'cx>) -> Expr {
-        self.hir.to_expr(cx, ToExprOptions { alpha: false })
+    /// Converts a value back to the corresponding AST expression, alpha-normalizing binders.
+    fn to_expr_alpha(&self, cx: Ctxt<'cx>) -> Expr {
+        self.hir.to_expr(cx, ToExprOptions { alpha: true })
+    }
+    /// The hash that an import of this expression must have, i.e. the hash of the
+    /// alpha-normalized expression.
+    fn semantic_hash(&self, cx: Ctxt<'cx>) -> Result<Hash, Error> {
+        Ok(Hash::SHA256(self.to_expr_alpha(cx).sha256_hash()?))
     }
 
     pub fn as_hir(&self) -> &Hir<'cx> {
@@ -0,0 +1,272 @@
+//! Command-line tool for the dhall crate.
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use dhall::semantics::{Bundle, Cache, CacheEntry, FreezeOptions};
+use dhall::{Ctxt, Parsed};
+
+const USAGE: &str = "\
+Usage: dhall cache [--cache-dir DIR] COMMAND
+       dhall depfile [--output FILE] [--env-vars] DHALL_FILE TARGET
+       dhall freeze [--all] [--no-cache] [--inplace] DHALL_FILE
+
+Run `dhall COMMAND --help` for details.";
+
+const DEPFILE_USAGE: &str = "\
+Usage: dhall depfile [--output FILE] [--env-vars] DHALL_FILE TARGET
+
+Resolve the imports of DHALL_FILE and print a rule in the Makefile syntax that
+makes TARGET depend on the local files that were read, for use as a depfile by
+make or ninja.
+
+Options:
+    --output FILE   Write the rule to FILE instead of the standard output
+    --env-vars      Also list the environment variables that were read, as
+                    comments. Make ignores them, but ninja rejects them.";
+
+const FREEZE_USAGE: &str = "\
+Usage: dhall freeze [--all] [--no-cache] [--inplace] DHALL_FILE
+
+Add sha256 integrity checks to the remote imports of DHALL_FILE and print the
+result. Comments and formatting are kept.
+
+Options:
+    --all           Also freeze local files and environment variables
+    --no-cache      Don't add the values of the imports to the cache
+    --inplace       Overwrite DHALL_FILE instead of printing the result";
+
+const CACHE_USAGE: &str = "\
+Usage: dhall cache [--cache-dir DIR] COMMAND
+
+Maintain the cache of imports protected by a hash, and the semi-semantic cache.
+The default cache directory is $XDG_CACHE_HOME/dhall, or $HOME/.cache/dhall.
+
+Commands:
+    list                List the entries of the cache
+    verify [--delete]   Check that every entry matches its hash, and optionally
+                        delete the ones that don't
+    prune [--max-age-days DAYS] [--max-size SIZE]
+                        Delete the entries that weren't accessed in the last
+                        DAYS days, then the least recently accessed ones until
+                        the cache is at most SIZE bytes. SIZE may end with K, M
+                        or G.
+    export FILE         Write the valid entries to a bundle file, except those of
+                        the semi-semantic cache
+    import FILE         Add the entries of a bundle file to the cache";
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> CliResult<()> {
+    match args.first().map(String::as_str) {
+        Some("cache") => cache_command(&args[1..]),
+        Some("depfile") => depfile_command(&args[1..]),
+        Some("freeze") => freeze_command(&args[1..]),
+        Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+/// Command-line arguments, split into flags and positional arguments.
+struct Args<'a> {
+    /// Flags with their value, if they take one.
+    flags: Vec<(&'a str, Option<&'a str>)>,
+    positional: Vec<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    /// `flags` take a value; `switches` don't. `usage` is shown for unknown options.
+    fn parse(
+        args: &'a [String],
+        flags: &[&str],
+        switches: &[&str],
+        usage: &str,
+    ) -> CliResult<Self> {
+        let mut found = Vec::new();
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_str();
+            if flags.contains(&arg) {
+                match args.next() {
+                    Some(value) => found.push((arg, Some(value.as_str()))),
+                    None => {
+                        return Err(format!("missing value for {}", arg).into())
+                    }
+                }
+            } else if switches.contains(&arg) {
+                found.push((arg, None));
+            } else if arg.starts_with("--") {
+                return Err(
+                    format!("unknown option {}\n\n{}", arg, usage).into()
+                );
+            } else {
+                positional.push(arg);
+            }
+        }
+        Ok(Args {
+            flags: found,
+            positional,
+        })
+    }
+
+    /// Whether the flag is present.
+    fn has(&self, name: &str) -> bool {
+        self.flags.iter().any(|(f, _)| *f == name)
+    }
+
+    /// The value of the flag, if it is present.
+    fn value(&self, name: &str) -> Option<&'a str> {
+        self.flags
+            .iter()
+            .find(|(f, _)| *f == name)
+            .and_then(|(_, value)| *value)
+    }
+}
+
+fn cache_command(args: &[String]) -> CliResult<()> {
+    let flags = ["--cache-dir", "--max-age-days", "--max-size"];
+    let args = Args::parse(args, &flags, &["--delete", "--help"], CACHE_USAGE)?;
+    if args.has("--help") {
+        println!("{}", CACHE_USAGE);
+        return Ok(());
+    }
+
+    let cache = match args.value("--cache-dir") {
+        Some(dir) => Cache::at(PathBuf::from(dir)),
+        None => Cache::new()?,
+    };
+
+    match args.positional.as_slice() {
+        ["list"] => {
+            for entry in cache.entries()? {
+                print_entry(&entry);
+            }
+        }
+        ["verify"] => {
+            let invalid = cache.verify()?;
+            for entry in &invalid {
+                print_entry(entry);
+                if args.has("--delete") {
+                    cache.remove(entry)?;
+                }
+            }
+            if !invalid.is_empty() && !args.has("--delete") {
+                return Err(
+                    format!("{} invalid cache entries", invalid.len()).into()
+                );
+            }
+        }
+        ["prune"] => {
+            let mut removed = Vec::new();
+            if let Some(days) = args.value("--max-age-days") {
+                let secs = days
+                    .parse::<u64>()?
+                    .checked_mul(24 * 60 * 60)
+                    .ok_or("--max-age-days is too large")?;
+                let max_age = Duration::from_secs(secs);
+                removed.extend(cache.prune_older_than(max_age)?);
+            }
+            if let Some(size) = args.value("--max-size") {
+                removed.extend(cache.prune_to_size(parse_size(size)?)?);
+            }
+            for entry in &removed {
+                print_entry(entry);
+            }
+        }
+        ["export", file] => {
+            std::fs::write(file, cache.export()?.to_bytes()?)?;
+        }
+        ["import", file] => {
+            let count = cache.import(&Bundle::from_file(file)?)?;
+            println!("Added {} entries", count);
+        }
+        _ => return Err(CACHE_USAGE.into()),
+    }
+    Ok(())
+}
+
+fn depfile_command(args: &[String]) -> CliResult<()> {
+    let switches = ["--env-vars", "--help"];
+    let args = Args::parse(args, &["--output"], &switches, DEPFILE_USAGE)?;
+    if args.has("--help") {
+        println!("{}", DEPFILE_USAGE);
+        return Ok(());
+    }
+    let (file, target) = match args.positional.as_slice() {
+        [file, target] => (file, target),
+        _ => return Err(DEPFILE_USAGE.into()),
+    };
+
+    let depfile = Ctxt::with_new(|cx| -> CliResult<String> {
+        let graph = Parsed::parse_file(Path::new(file))?
+            .resolve(cx)?
+            .import_graph(cx);
+        let mut depfile = graph.to_depfile(target);
+        if args.has("--env-vars") {
+            for var in graph.read_env_vars() {
+                depfile.push_str(&format!("# env:{}\n", var));
+            }
+        }
+        Ok(depfile)
+    })?;
+    match args.value("--output") {
+        Some(output) => std::fs::write(output, depfile)?,
+        None => print!("{}", depfile),
+    }
+    Ok(())
+}
+
+fn freeze_command(args: &[String]) -> CliResult<()> {
+    let switches = ["--all", "--no-cache", "--inplace", "--help"];
+    let args = Args::parse(args, &[], &switches, FREEZE_USAGE)?;
+    if args.has("--help") {
+        println!("{}", FREEZE_USAGE);
+        return Ok(());
+    }
+    let file = match args.positional.as_slice() {
+        [file] => file,
+        _ => return Err(FREEZE_USAGE.into()),
+    };
+
+    let options = FreezeOptions::default()
+        .all_imports(args.has("--all"))
+        .cache(!args.has("--no-cache"));
+    let frozen = Ctxt::with_new(|cx| -> CliResult<String> {
+        Ok(Parsed::parse_file(Path::new(file))?.freeze_source(cx, options)?)
+    })?;
+    if args.has("--inplace") {
+        std::fs::write(file, frozen)?;
+    } else {
+        print!("{}", frozen);
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &CacheEntry) {
+    println!("{}\t{}", entry.hash, entry.size);
+}
+
+fn parse_size(size: &str) -> CliResult<u64> {
+    let (digits, unit) = match size.char_indices().last() {
+        Some((i, 'K')) => (&size[..i], 1 << 10),
+        Some((i, 'M')) => (&size[..i], 1 << 20),
+        Some((i, 'G')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    Ok(digits
+        .parse::<u64>()?
+        .checked_mul(unit)
+        .ok_or("--max-size is too large")?)
+}
@@ -47,6 +47,8 @@ pub enum OpKind<SubExpr> {
     Merge(SubExpr, SubExpr, Option<SubExpr>),
     ///  `toMap x : t`
     ToMap(SubExpr, Option<SubExpr>),
+    ///  `showConstructor x`
+    ShowConstructor(SubExpr),
     ///  `e.x`
     Field(SubExpr, Label),
     ///  `e.{ x, y, z }`
@@ -83,6 +85,7 @@ impl<SE> OpKind<SE> {
             BoolIf(b, t, f) => BoolIf(expr!(b), expr!(t), expr!(f)),
             Merge(x, y, t) => Merge(expr!(x), expr!(y), opt!(t)),
             ToMap(x, t) => ToMap(expr!(x), opt!(t)),
+            ShowConstructor(x) => ShowConstructor(expr!(x)),
             Field(e, l) => Field(expr!(e), l.clone()),
             Projection(e, ls) => Projection(expr!(e), ls.clone()),
             ProjectionByExpr(e, x) => ProjectionByExpr(expr!(e), expr!(x)),
@@ -6,7 +6,7 @@ use crate::operations::{BinOp, OpKind};
 use crate::semantics::{
     merge_maps, ret_kind, ret_nir, ret_op, ret_ref, Nir, NirKind, Ret, TextLit,
 };
-use crate::syntax::{ExprKind, Label, NumKind};
+use crate::syntax::{Expr, ExprKind, Label, NumKind};
 
 fn normalize_binop<'cx>(o: BinOp, x: Nir<'cx>, y: Nir<'cx>) -> Ret<'cx> {
     use BinOp::*;
@@ -35,15 +35,22 @@ fn normalize_binop<'cx>(o: BinOp, x: Nir<'cx>, y: Nir<'cx>) -> Ret<'cx> {
 
         (NaturalPlus, Num(Natural(0)), _) => ret_nir(y),
         (NaturalPlus, _, Num(Natural(0))) => ret_nir(x),
-        (NaturalPlus, Num(Natural(x)), Num(Natural(y))) => {
-            ret_kind(Num(Natural(x + y)))
+        (NaturalPlus, Num(Natural(a)), Num(Natural(b))) => {
+            match a.checked_add(*b) {
+                Some(n) => ret_kind(Num(Natural(n))),
+                // Leave overflowing arithmetic unevaluated; see `find_overflow`.
+                None => ret_op(OpKind::BinOp(o, x.clone(), y.clone())),
+            }
         }
         (NaturalTimes, Num(Natural(0)), _) => ret_kind(Num(Natural(0))),
         (NaturalTimes, _, Num(Natural(0))) => ret_kind(Num(Natural(0))),
         (NaturalTimes, Num(Natural(1)), _) => ret_nir(y),
         (NaturalTimes, _, Num(Natural(1))) => ret_nir(x),
-        (NaturalTimes, Num(Natural(x)), Num(Natural(y))) => {
-            ret_kind(Num(Natural(x * y)))
+        (NaturalTimes, Num(Natural(a)), Num(Natural(b))) => {
+            match a.checked_mul(*b) {
+                Some(n) => ret_kind(Num(Natural(n))),
+                None => ret_op(OpKind::BinOp(o, x.clone(), y.clone())),
+            }
         }
 
         (ListAppend, EmptyListLit(_), _) => ret_nir(y),
@@ -257,6 +264,14 @@ pub fn normalize_operation<'cx>(opkind: OpKind<Nir<'cx>>) -> Ret<'cx> {
             )),
             _ => ret_op(ToMap(v, annot)),
         },
+        ShowConstructor(v) => match v.kind() {
+            UnionConstructor(l, _) | UnionLit(l, _, _) => {
+                ret_nir(Nir::from_text(l))
+            }
+            EmptyOptionalLit(_) => ret_nir(Nir::from_text("None")),
+            NEOptionalLit(_) => ret_nir(Nir::from_text("Some")),
+            _ => ret_op(ShowConstructor(v)),
+        },
         Field(v, field) => normalize_field(&v, &field),
         Projection(_, ls) if ls.is_empty() => {
             ret_kind(RecordLit(HashMap::new()))
@@ -330,3 +345,29 @@ pub fn normalize_operation<'cx>(opkind: OpKind<Nir<'cx>>) -> Ret<'cx> {
         }
     }
 }
+
+/// Find a subexpression that normalization left unevaluated because its result does not fit in
+/// the 64 bits of a `Natural` or an `Integer`, like `18446744073709551615 + 1`.
+pub(crate) fn find_overflow(expr: &Expr) -> Option<&Expr> {
+    use crate::builtins::Builtin::{IntegerNegate, NaturalToInteger};
+    use ExprKind::{Builtin, Num, Op};
+    use NumKind::{Integer, Natural};
+    let overflows = match expr.kind() {
+        Op(OpKind::BinOp(BinOp::NaturalPlus, x, y))
+        | Op(OpKind::BinOp(BinOp::NaturalTimes, x, y)) => {
+            matches!((x.kind(), y.kind()), (Num(Natural(_)), Num(Natural(_))))
+        }
+        Op(OpKind::App(f, x)) => matches!(
+            (f.kind(), x.kind()),
+            (Builtin(NaturalToInteger), Num(Natural(_)))
+                | (Builtin(IntegerNegate), Num(Integer(_)))
+        ),
+        _ => false,
+    };
+    if overflows {
+        return Some(expr);
+    }
+    expr.kind()
+        .traverse_ref(|e| find_overflow(e).map_or(Ok(()), Err))
+        .err()
+}
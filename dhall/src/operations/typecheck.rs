@@ -11,6 +11,7 @@ use crate::semantics::{
 };
 use crate::syntax::{Const, ExprKind, Span};
 
+#[allow(clippy::only_used_in_recursion)]
 fn check_rectymerge(
     span: &Span,
     env: &TyEnv<'_>,
@@ -296,7 +297,7 @@ pub fn typecheck_operation<'cx>(
 ) -> Result<Type<'cx>, TypeError> {
     let cx = env.cx();
     let span_err = |msg: &str| mk_span_err(span.clone(), msg);
-    use NirKind::{ListType, PiClosure, RecordType, UnionType};
+    use NirKind::{ListType, OptionalType, PiClosure, RecordType, UnionType};
     use OpKind::*;
 
     Ok(match opkind {
@@ -438,6 +439,17 @@ pub fn typecheck_operation<'cx>(
                 output_type
             }
         }
+        ShowConstructor(x) => {
+            match x.ty().kind() {
+                UnionType(_) | OptionalType(_) => {
+                    Nir::from_builtin(cx, Builtin::Text).to_type(Const::Type)
+                }
+                _ => return span_err(
+                    "The argument to `showConstructor` must be a union or an \
+                     `Optional`",
+                ),
+            }
+        }
         Field(scrut, x) => {
             match scrut.ty().kind() {
                 RecordType(kts) => match kts.get(&x) {
@@ -2,6 +2,7 @@ pub mod nze;
 pub mod parse;
 pub mod resolve;
 pub mod tck;
+#[allow(ambiguous_glob_reexports)]
 pub use self::nze::*;
 pub use self::resolve::*;
 pub use self::tck::*;
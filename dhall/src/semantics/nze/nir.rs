@@ -120,7 +120,7 @@ impl<'cx> Nir<'cx> {
     }
 
     pub fn as_const(&self) -> Option<Const> {
-        match &*self.kind() {
+        match self.kind() {
             NirKind::Const(c) => Some(*c),
             _ => None,
         }
@@ -128,7 +128,7 @@ impl<'cx> Nir<'cx> {
 
     /// This is what you want if you want to pattern-match on the value.
     pub fn kind(&self) -> &NirKind<'cx> {
-        &*self.0
+        &self.0
     }
 
     /// The contents of a `Nir` are immutable and shared. If however we happen to be the sole
@@ -2,19 +2,28 @@ use std::path::Path;
 use url::Url;
 
 use crate::error::Error;
-use crate::semantics::resolve::{download_http_text, ImportLocation};
+use crate::semantics::resolve::{
+    DefaultFetcher, ImportFetcher, ImportLocation,
+};
 use crate::syntax::{binary, parse_expr};
 use crate::Parsed;
 
 pub fn parse_file(f: &Path) -> Result<Parsed, Error> {
-    let text = std::fs::read_to_string(f)?;
+    parse_file_with(f, &DefaultFetcher)
+}
+
+pub fn parse_file_with(
+    f: &Path,
+    fetcher: &dyn ImportFetcher,
+) -> Result<Parsed, Error> {
+    let text = fetcher.fetch_local(f)?;
     let expr = parse_expr(&text)?;
     let root = ImportLocation::local_dhall_code(f.to_owned());
     Ok(Parsed(expr, root))
 }
 
 pub fn parse_remote(url: Url) -> Result<Parsed, Error> {
-    let body = download_http_text(url.clone())?;
+    let body = DefaultFetcher.fetch_remote(&url, &[])?.text;
     let expr = parse_expr(&body)?;
     let root = ImportLocation::remote_dhall_code(url);
     Ok(Parsed(expr, root))
@@ -0,0 +1,101 @@
+use serde_cbor::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{DecodeError, EncodeError, Error};
+use crate::semantics::resolve::cache::decode_entry;
+use crate::syntax::Hash;
+use crate::{Ctxt, Typed};
+
+/// A set of binary-encoded expressions keyed by their hash, like the entries of the on-disk
+/// cache, that can be stored in a single file.
+///
+/// The file is a CBOR array of byte strings, each holding the binary encoding of an
+/// expression. Hashes are recomputed when loading, so a bundle can't contain wrong entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bundle {
+    entries: HashMap<Hash, Box<[u8]>>,
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Bundle::default()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Bundle::from_bytes(&crate::utils::read_binary_file(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let entries = match serde_cbor::from_slice(data)
+            .map_err(DecodeError::CBORError)?
+        {
+            Value::Array(entries) => entries,
+            _ => {
+                return Err(DecodeError::WrongFormatError(
+                    "bundle should be an array".to_owned(),
+                )
+                .into())
+            }
+        };
+        let mut bundle = Bundle::new();
+        for entry in entries {
+            match entry {
+                Value::Bytes(data) => {
+                    bundle.insert(data.into());
+                }
+                _ => {
+                    return Err(DecodeError::WrongFormatError(
+                        "bundle entries should be byte strings".to_owned(),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(bundle)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let entries = self
+            .entries
+            .values()
+            .map(|data| Value::Bytes(data.to_vec()))
+            .collect();
+        Ok(serde_cbor::to_vec(&Value::Array(entries))
+            .map_err(EncodeError::CBORError)?)
+    }
+
+    /// Add the binary encoding of an expression to the bundle. Returns its hash.
+    pub fn insert(&mut self, data: Box<[u8]>) -> Hash {
+        let hash = Hash::SHA256(crate::utils::sha256_hash(&data));
+        self.entries.insert(hash.clone(), data);
+        hash
+    }
+
+    /// Iterate over the hashes and binary encodings of the entries.
+    pub fn iter(&self) -> impl Iterator<Item = (&Hash, &[u8])> {
+        self.entries.iter().map(|(hash, data)| (hash, &data[..]))
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Decode the entry with the given hash, if there is one.
+    pub fn get<'cx>(
+        &self,
+        cx: Ctxt<'cx>,
+        hash: &Hash,
+    ) -> Option<Result<Typed<'cx>, Error>> {
+        let data = self.entries.get(hash)?;
+        Some(decode_entry(cx, data))
+    }
+}
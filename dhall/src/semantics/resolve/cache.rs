@@ -1,13 +1,19 @@
 use std::env;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
 use crate::error::{CacheError, Error};
 use crate::parse::parse_binary;
+use crate::semantics::Bundle;
 use crate::syntax::{binary, Hash};
 use crate::{Ctxt, Typed};
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Subdirectory of the cache directory holding the semi-semantic cache.
+const SEMI_SEMANTIC_DIR: &str = "semi-semantic";
 
 #[cfg(any(unix, windows))]
 const CACHE_ENV_VAR: &str = "XDG_CACHE_HOME";
@@ -33,35 +39,80 @@ fn default_cache_dir() -> Result<PathBuf, CacheError> {
     Err(CacheError::MissingConfiguration)
 }
 
+/// The on-disk cache of hashed imports. The cache directory is only created when the first
+/// entry is written.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cache {
     cache_dir: PathBuf,
+    read_only: bool,
 }
 
 impl Cache {
+    /// The cache in the default location: `$XDG_CACHE_HOME/dhall`, or `$HOME/.cache/dhall`.
     pub fn new() -> Result<Cache, Error> {
-        let cache_dir = default_cache_dir()?;
-        if !cache_dir.exists() {
-            std::fs::create_dir_all(&cache_dir)
-                .map_err(|e| CacheError::InitialisationError { cause: e })?;
+        Ok(Cache::at(default_cache_dir()?))
+    }
+
+    /// The cache in the given directory.
+    pub fn at(cache_dir: impl Into<PathBuf>) -> Cache {
+        Cache {
+            cache_dir: cache_dir.into(),
+            read_only: false,
         }
-        Ok(Cache { cache_dir })
+    }
+
+    /// A read-only cache is never written to, and invalid entries are left in place.
+    pub fn read_only(self, read_only: bool) -> Self {
+        Cache { read_only, ..self }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
     }
 
     fn entry_path(&self, hash: &Hash) -> PathBuf {
         self.cache_dir.join(filename_for_hash(hash))
     }
 
+    fn semi_semantic_dir(&self) -> PathBuf {
+        self.cache_dir.join(SEMI_SEMANTIC_DIR)
+    }
+
     pub fn get<'cx>(
         &self,
         cx: Ctxt<'cx>,
         hash: &Hash,
     ) -> Result<Typed<'cx>, Error> {
-        let path = self.entry_path(hash);
-        let res = read_cache_file(cx, &path, hash);
-        if res.is_err() && path.exists() {
-            // Delete cache file since it's invalid. We ignore the error.
-            let _ = std::fs::remove_file(&path);
+        self.read_entry(&self.entry_path(hash), |data| {
+            check_hash(data, hash)?;
+            decode_entry(cx, data)
+        })
+    }
+
+    /// Read the entry at `path` with `decode`. If that fails, the entry is deleted, unless the
+    /// cache is read-only or a writer replaced it in the meantime.
+    fn read_entry<T>(
+        &self,
+        path: &Path,
+        decode: impl FnOnce(&[u8]) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut file = File::open(path)?;
+        let mut data = Vec::new();
+        let res = match file.read_to_end(&mut data) {
+            Ok(_) => decode(&data),
+            Err(e) => Err(e.into()),
+        };
+        if !self.read_only {
+            if res.is_ok() {
+                touch(path);
+            } else if is_same_file(&file, path) {
+                // Delete cache file since it's invalid. We ignore the error.
+                let _ = std::fs::remove_file(path);
+            }
         }
         res
     }
@@ -72,48 +123,329 @@ impl Cache {
         hash: &Hash,
         expr: &Typed<'cx>,
     ) -> Result<(), Error> {
-        let path = self.entry_path(hash);
-        write_cache_file(cx, &path, expr)
+        // Entries are named after the hash of the alpha-normalized expression.
+        let data = binary::encode(&expr.to_expr_alpha(cx))?;
+        self.insert_data(hash, &data)
+    }
+
+    fn insert_data(&self, hash: &Hash, data: &[u8]) -> Result<(), Error> {
+        if self.read_only {
+            return Ok(());
+        }
+        create_dir(&self.cache_dir)?;
+        write_cache_file(&self.entry_path(hash), data)
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.entry_path(hash).exists()
+    }
+
+    /// Get an entry of the semi-semantic cache.
+    ///
+    /// Unlike normal entries, these are keyed by the hash of the source of an import and of
+    /// the values of its dependencies rather than by the hash of their contents. They are stored
+    /// in the `semi-semantic` subdirectory.
+    pub fn get_semi_semantic<'cx>(
+        &self,
+        cx: Ctxt<'cx>,
+        key: &Hash,
+    ) -> Result<Typed<'cx>, Error> {
+        let path = self.semi_semantic_dir().join(filename_for_hash(key));
+        self.read_entry(&path, |data| decode_entry(cx, data))
+    }
+
+    pub fn insert_semi_semantic<'cx>(
+        &self,
+        cx: Ctxt<'cx>,
+        key: &Hash,
+        expr: &Typed<'cx>,
+    ) -> Result<(), Error> {
+        if self.read_only {
+            return Ok(());
+        }
+        let dir = self.semi_semantic_dir();
+        create_dir(&dir)?;
+        let data = binary::encode(&expr.to_expr_alpha(cx))?;
+        write_cache_file(&dir.join(filename_for_hash(key)), &data)
+    }
+
+    /// List the entries of the cache, including those of the semi-semantic cache. Files that
+    /// aren't named like cache entries are ignored.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, Error> {
+        let mut entries = list_entries(&self.cache_dir, false)?;
+        entries.extend(list_entries(&self.semi_semantic_dir(), true)?);
+        Ok(entries)
+    }
+
+    /// Check that the contents of every entry match its hash. Returns the invalid entries.
+    pub fn verify(&self) -> Result<Vec<CacheEntry>, Error> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| !entry.is_valid())
+            .collect())
+    }
+
+    /// Delete an entry from the cache.
+    pub fn remove(&self, entry: &CacheEntry) -> Result<(), Error> {
+        if self.read_only {
+            return Err(CacheError::ReadOnly.into());
+        }
+        std::fs::remove_file(&entry.path)?;
+        Ok(())
+    }
+
+    /// Delete the entries that haven't been accessed for longer than `max_age`. Returns the
+    /// deleted entries.
+    pub fn prune_older_than(
+        &self,
+        max_age: Duration,
+    ) -> Result<Vec<CacheEntry>, Error> {
+        let now = SystemTime::now();
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            let age = now.duration_since(entry.last_access).unwrap_or_default();
+            if age > max_age {
+                self.remove(&entry)?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Delete the least recently accessed entries until the cache takes at most `max_size`
+    /// bytes. Returns the deleted entries.
+    pub fn prune_to_size(
+        &self,
+        max_size: u64,
+    ) -> Result<Vec<CacheEntry>, Error> {
+        let mut entries = self.entries()?;
+        let mut size: u64 = entries.iter().map(|entry| entry.size).sum();
+        entries.sort_by_key(|entry| entry.last_access);
+        let mut removed = Vec::new();
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            self.remove(&entry)?;
+            size -= entry.size;
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+
+    /// Collect the valid entries of the cache into a bundle. Entries of the semi-semantic cache
+    /// are left out, since a bundle only holds entries named after the hash of their contents.
+    pub fn export(&self) -> Result<Bundle, Error> {
+        let mut bundle = Bundle::new();
+        for entry in self.entries()? {
+            if entry.semi_semantic {
+                continue;
+            }
+            let data = crate::utils::read_binary_file(&entry.path)?;
+            if entry.hash_matches(&data) {
+                bundle.insert(data);
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Add the entries of a bundle to the cache. Returns the number of new entries.
+    pub fn import(&self, bundle: &Bundle) -> Result<usize, Error> {
+        if self.read_only {
+            return Err(CacheError::ReadOnly.into());
+        }
+        let mut count = 0;
+        for (hash, data) in bundle.iter() {
+            if !self.entry_path(hash).exists() {
+                self.insert_data(hash, data)?;
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 }
 
-/// Read a file from the cache, also checking that its hash is valid.
-fn read_cache_file<'cx>(
-    cx: Ctxt<'cx>,
-    path: &Path,
-    hash: &Hash,
-) -> Result<Typed<'cx>, Error> {
-    let data = crate::utils::read_binary_file(path)?;
+/// A file in the on-disk cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub hash: Hash,
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+    pub last_access: SystemTime,
+    /// Whether this is an entry of the semi-semantic cache. Its `hash` is then the key of the
+    /// entry, not the hash of its contents.
+    pub semi_semantic: bool,
+}
+
+impl CacheEntry {
+    /// Whether the contents of the file match its hash. Entries of the semi-semantic cache can't
+    /// be checked against their key, so they are valid if they decode to an expression.
+    pub fn is_valid(&self) -> bool {
+        match crate::utils::read_binary_file(&self.path) {
+            Ok(data) if self.semi_semantic => binary::decode(&data).is_ok(),
+            Ok(data) => self.hash_matches(&data),
+            Err(_) => false,
+        }
+    }
+
+    fn hash_matches(&self, data: &[u8]) -> bool {
+        match &self.hash {
+            Hash::SHA256(hash) => {
+                hash[..] == crate::utils::sha256_hash(data)[..]
+            }
+        }
+    }
+}
+
+/// List the cache entries directly inside `dir`.
+fn list_entries(
+    dir: &Path,
+    semi_semantic: bool,
+) -> Result<Vec<CacheEntry>, Error> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let file = file?;
+        let hash = match file.file_name().to_str().and_then(hash_for_filename) {
+            Some(hash) => hash,
+            None => continue,
+        };
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        // Access times are often not updated on every read, so don't let them be older than
+        // the last write.
+        let modified = metadata.modified()?;
+        let last_access = match metadata.accessed() {
+            Ok(accessed) if accessed > modified => accessed,
+            _ => modified,
+        };
+        entries.push(CacheEntry {
+            hash,
+            path: file.path(),
+            size: metadata.len(),
+            last_access,
+            semi_semantic,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
 
+/// Record that an entry was just read, so that pruning goes by last access. We can't count on
+/// the filesystem for this: access times are often disabled or only updated once a day. We
+/// ignore errors.
+fn touch(path: &Path) {
+    let _ = filetime::set_file_atime(path, filetime::FileTime::now());
+}
+
+/// Check that the contents of an entry have the hash it is named after.
+fn check_hash(data: &[u8], hash: &Hash) -> Result<(), Error> {
     match hash {
         Hash::SHA256(hash) => {
-            let actual_hash = crate::utils::sha256_hash(&data);
+            let actual_hash = crate::utils::sha256_hash(data);
             if hash[..] != actual_hash[..] {
                 return Err(CacheError::CacheHashInvalid.into());
             }
         }
     }
+    Ok(())
+}
 
-    Ok(parse_binary(&data)?.resolve(cx)?.typecheck(cx)?)
+/// Whether `path` is still the file that `file` was opened from. Writers replace entries by
+/// renaming a new file over them.
+fn is_same_file(file: &File, path: &Path) -> bool {
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(opened), Ok(current)) => same_file_metadata(&opened, &current),
+        _ => false,
+    }
 }
 
-/// Write a file to the cache.
-fn write_cache_file<'cx>(
+#[cfg(unix)]
+fn same_file_metadata(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file_metadata(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    a.len() == b.len() && a.modified().ok() == b.modified().ok()
+}
+
+/// Decode the contents of a cache entry.
+pub(crate) fn decode_entry<'cx>(
     cx: Ctxt<'cx>,
-    path: &Path,
-    expr: &Typed<'cx>,
-) -> Result<(), Error> {
-    let data = binary::encode(&expr.to_expr(cx))?;
-    File::create(path)?.write_all(data.as_slice())?;
+    data: &[u8],
+) -> Result<Typed<'cx>, Error> {
+    Ok(parse_binary(data)?.resolve(cx)?.typecheck(cx)?)
+}
+
+fn create_dir(dir: &Path) -> Result<(), Error> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| CacheError::InitialisationError { cause: e })?;
+    }
     Ok(())
 }
 
-fn filename_for_hash(hash: &Hash) -> String {
+/// Write a file to the cache.
+///
+/// The data is first written to a temporary file in the same directory, which is then renamed
+/// to `path`. Since renaming is atomic, concurrent readers never see a partially written entry,
+/// and concurrent writers of the same entry don't interfere.
+fn write_cache_file(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let tmp_path = temp_path_for(path);
+    let res = write_and_rename(&tmp_path, path, data);
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    Ok(res?)
+}
+
+fn write_and_rename(
+    tmp_path: &Path,
+    path: &Path,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut file = File::create(tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)
+}
+
+/// A path next to `path` that no other thread or process is writing to.
+fn temp_path_for(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.{}.tmp", std::process::id(), count));
+    path.with_file_name(name)
+}
+
+pub fn filename_for_hash(hash: &Hash) -> String {
     match hash {
-        Hash::SHA256(sha) => format!("1220{}", hex::encode(&sha)),
+        Hash::SHA256(sha) => format!("1220{}", hex::encode(sha)),
     }
 }
 
+/// The inverse of `filename_for_hash`.
+pub fn hash_for_filename(name: &str) -> Option<Hash> {
+    if !name.starts_with("1220") {
+        return None;
+    }
+    let sha = hex::decode(&name[4..]).ok()?;
+    if sha.len() != 32 {
+        return None;
+    }
+    Some(Hash::SHA256(sha.into()))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -125,4 +457,13 @@ mod test {
             Hash::SHA256(parse_expr("1").unwrap().sha256_hash().unwrap());
         assert_eq!("1220d60d8415e36e86dae7f42933d3b0c4fe3ca238f057fba206c7e9fbf5d784fe15".to_string(), filename_for_hash(&hash));
     }
+
+    #[test]
+    fn hash_for_filename_should_work() {
+        let hash =
+            Hash::SHA256(parse_expr("1").unwrap().sha256_hash().unwrap());
+        assert_eq!(hash_for_filename(&filename_for_hash(&hash)), Some(hash));
+        assert_eq!(hash_for_filename("1220d60d"), None);
+        assert_eq!(hash_for_filename(".1220d60d.tmp"), None);
+    }
 }
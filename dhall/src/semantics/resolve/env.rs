@@ -1,8 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use url::Url;
 
 use crate::error::{Error, ImportError};
-use crate::semantics::{check_hash, AlphaVar, Cache, ImportLocation, VarEnv};
-use crate::syntax::{Hash, Label, V};
+use crate::semantics::{
+    check_hash, AlphaVar, Bundle, Cache, DefaultFetcher, Headers,
+    ImportFetcher, ImportLocation, ImportPolicy, Lockfile, LockfileMode,
+    RemoteFile, RemoteFiles, VarEnv,
+};
+use crate::standard::StandardVersion;
+use crate::syntax::{Hash, ImportMode, Label, Span, V};
 use crate::{Ctxt, ImportId, ImportResultId, Typed};
 
 /// Environment for resolving names.
@@ -11,13 +17,51 @@ pub struct NameEnv {
     names: Vec<Label>,
 }
 
-pub type CyclesStack = Vec<ImportLocation>;
+/// An import being resolved: the location it points to, and where it appears in the importing
+/// file.
+#[derive(Clone)]
+pub struct ImportFrame {
+    pub location: ImportLocation,
+    pub span: Span,
+}
+
+/// The imports being resolved, outermost first.
+pub type CyclesStack = Vec<ImportFrame>;
+
+/// Leaves out the span, whose `Debug` output includes the whole source file.
+impl std::fmt::Debug for ImportFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ImportFrame")
+            .field("location", &self.location)
+            .finish()
+    }
+}
 
 /// Environment for resolving imports
 pub struct ImportEnv<'cx> {
     cx: Ctxt<'cx>,
+    fetcher: Box<dyn ImportFetcher>,
+    policy: ImportPolicy,
+    /// Whether remote imports must be served from the cache or the bundle.
+    offline: bool,
+    bundle: Option<Bundle>,
+    lockfile: Option<(Lockfile, LockfileMode)>,
+    semi_semantic_cache: bool,
+    standard_version: Option<StandardVersion>,
     disk_cache: Option<Cache>, // `None` if it failed to initialize
     mem_cache: HashMap<ImportLocation, ImportResultId<'cx>>,
+    /// Remote files downloaded ahead of time, that haven't been used yet.
+    prefetched: HashMap<(Url, Headers), Result<RemoteFile, Error>>,
+    /// If set, remote files are only taken from there.
+    remote_files: Option<RemoteFiles>,
+    /// The remote files that were needed but not found in `remote_files`.
+    missing_remote_files: Vec<(Url, Headers)>,
+    /// Whether we only look for the remote files that resolution needs; see `discovering`.
+    discovering: bool,
+    /// The locations whose imports were already discovered.
+    discovered: HashSet<ImportLocation>,
+    /// Imports whose value is needed to resolve the others, even while discovering.
+    value_needed: HashSet<ImportId<'cx>>,
     stack: CyclesStack,
 }
 
@@ -69,16 +113,253 @@ impl<'cx> ImportEnv<'cx> {
     pub fn new(cx: Ctxt<'cx>) -> Self {
         ImportEnv {
             cx,
+            fetcher: Box::new(DefaultFetcher),
+            policy: ImportPolicy::default(),
+            offline: false,
+            bundle: None,
+            lockfile: None,
+            semi_semantic_cache: false,
+            standard_version: None,
             disk_cache: Cache::new().ok(),
             mem_cache: Default::default(),
+            prefetched: Default::default(),
+            remote_files: None,
+            missing_remote_files: Vec::new(),
+            discovering: false,
+            discovered: Default::default(),
+            value_needed: Default::default(),
             stack: Default::default(),
         }
     }
 
+    /// Use the provided fetcher to get the contents of imports. If the fetcher doesn't use the
+    /// default cache, like `VirtualFiles`, the on-disk cache is turned off; call `with_cache`
+    /// afterwards to use one anyway.
+    pub fn with_fetcher(self, fetcher: impl ImportFetcher + 'static) -> Self {
+        let disk_cache = if fetcher.use_default_cache() {
+            self.disk_cache
+        } else {
+            None
+        };
+        ImportEnv {
+            fetcher: Box::new(fetcher),
+            disk_cache,
+            ..self
+        }
+    }
+
+    /// Reject imports that `policy` doesn't allow.
+    pub fn with_policy(self, policy: ImportPolicy) -> Self {
+        ImportEnv { policy, ..self }
+    }
+
+    /// In offline mode, remote imports are never downloaded: they must have a hash and be found
+    /// in the on-disk cache or in the bundle.
+    pub fn offline(self, offline: bool) -> Self {
+        ImportEnv { offline, ..self }
+    }
+
+    /// Use `cache` as the on-disk cache instead of the default one.
+    pub fn with_cache(self, cache: Cache) -> Self {
+        ImportEnv {
+            disk_cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Don't read from or write to the on-disk cache.
+    pub fn without_cache(self) -> Self {
+        ImportEnv {
+            disk_cache: None,
+            ..self
+        }
+    }
+
+    /// Also cache imports that aren't protected by a hash, keyed by their source and the values
+    /// of their own imports. When neither changed, the cached value is used instead of
+    /// typechecking and normalizing the import again. The imports it contains are still
+    /// resolved, since the key depends on their values. Disabled by default; has no effect
+    /// without an on-disk cache.
+    pub fn semi_semantic_cache(self, enabled: bool) -> Self {
+        ImportEnv {
+            semi_semantic_cache: enabled,
+            ..self
+        }
+    }
+
+    /// Reject expressions, including imported ones, that use features added after `version` of
+    /// the standard. Imports read from the cache or a bundle have no source, so only the
+    /// features their value still uses are detected.
+    pub fn with_standard_version(self, version: StandardVersion) -> Self {
+        ImportEnv {
+            standard_version: Some(version),
+            ..self
+        }
+    }
+
+    /// Take remote files from `files` instead of downloading them. Remote imports that aren't in
+    /// there fail like in offline mode, and are listed by `missing_remote_files`.
+    pub fn with_remote_files(self, files: RemoteFiles) -> Self {
+        ImportEnv {
+            remote_files: Some(files),
+            ..self
+        }
+    }
+
+    /// Only look for the remote files that resolution needs: the imports of each file are
+    /// resolved, but the file itself is not typechecked or normalized, unless its value is
+    /// needed to resolve other imports. The result of resolution can't be used.
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+    pub(crate) fn discovering(self) -> Self {
+        ImportEnv {
+            discovering: true,
+            ..self
+        }
+    }
+
+    /// Look up hashed imports in `bundle` when they're not in the on-disk cache.
+    pub fn with_bundle(self, bundle: Bundle) -> Self {
+        ImportEnv {
+            bundle: Some(bundle),
+            ..self
+        }
+    }
+
+    /// Pin remote imports with `lockfile`. See `Lockfile`.
+    pub fn with_lockfile(self, lockfile: Lockfile, mode: LockfileMode) -> Self {
+        ImportEnv {
+            lockfile: Some((lockfile, mode)),
+            ..self
+        }
+    }
+
     pub fn cx(&self) -> Ctxt<'cx> {
         self.cx
     }
 
+    pub fn fetcher(&self) -> &dyn ImportFetcher {
+        &*self.fetcher
+    }
+
+    pub fn policy(&self) -> &ImportPolicy {
+        &self.policy
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// The remote files that resolution needed but were not provided with `with_remote_files`.
+    pub fn missing_remote_files(&self) -> &[(Url, Headers)] {
+        &self.missing_remote_files
+    }
+
+    /// Get back the files passed to `with_remote_files`.
+    pub fn into_remote_files(self) -> RemoteFiles {
+        self.remote_files.unwrap_or_default()
+    }
+
+    pub fn lockfile(&self) -> Option<&Lockfile> {
+        self.lockfile.as_ref().map(|(lockfile, _)| lockfile)
+    }
+
+    /// Get back the lockfile passed to `with_lockfile`, with the entries added during
+    /// resolution.
+    pub fn into_lockfile(self) -> Option<Lockfile> {
+        self.lockfile.map(|(lockfile, _)| lockfile)
+    }
+
+    pub fn disk_cache(&self) -> Option<&Cache> {
+        self.disk_cache.as_ref()
+    }
+
+    pub fn uses_semi_semantic_cache(&self) -> bool {
+        self.semi_semantic_cache && self.disk_cache.is_some()
+    }
+
+    pub fn standard_version(&self) -> Option<StandardVersion> {
+        self.standard_version
+    }
+
+    pub(crate) fn is_discovering(&self) -> bool {
+        self.discovering
+    }
+
+    pub(crate) fn is_discovered(&self, location: &ImportLocation) -> bool {
+        self.discovered.contains(location)
+    }
+
+    /// Record that the imports of `location` were successfully discovered.
+    pub(crate) fn mark_discovered(&mut self, location: ImportLocation) {
+        self.discovered.insert(location);
+    }
+
+    /// Record that the value of this import is needed to resolve other imports.
+    pub(crate) fn mark_value_needed(&mut self, import: ImportId<'cx>) {
+        self.value_needed.insert(import);
+    }
+
+    pub(crate) fn is_value_needed(&self, import: ImportId<'cx>) -> bool {
+        self.value_needed.contains(&import)
+    }
+
+    /// Run `f` with discovery turned off, to compute the value of an import.
+    pub(crate) fn without_discovery<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let discovering = std::mem::replace(&mut self.discovering, false);
+        let res = f(self);
+        self.discovering = discovering;
+        res
+    }
+
+    /// Download a remote file, unless it was already downloaded by `prefetch_remote` or
+    /// provided with `with_remote_files`.
+    pub fn fetch_remote(
+        &mut self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error> {
+        if let Some(files) = &self.remote_files {
+            return match files.get(url, headers) {
+                Some(res) => res,
+                None => {
+                    let request = (url.clone(), headers.to_vec());
+                    if !self.missing_remote_files.contains(&request) {
+                        self.missing_remote_files.push(request);
+                    }
+                    Err(ImportError::NetworkDisabled(url.to_string()).into())
+                }
+            };
+        }
+        match self.prefetched.remove(&(url.clone(), headers.to_vec())) {
+            Some(res) => res,
+            None => self.fetcher.fetch_remote(url, headers),
+        }
+    }
+
+    /// Download these remote files together, keeping the results for later calls to
+    /// `fetch_remote`.
+    pub fn prefetch_remote(&mut self, requests: Vec<(Url, Headers)>) {
+        if self.remote_files.is_some() {
+            return;
+        }
+        let results = self.fetcher.fetch_remote_many(&requests);
+        self.prefetched.extend(requests.into_iter().zip(results));
+    }
+
+    /// Whether an import with this hash would be found in the on-disk cache or the bundle.
+    pub fn has_cached(&self, hash: &Option<Hash>) -> bool {
+        match hash {
+            Some(hash) => {
+                self.disk_cache.as_ref().map_or(false, |c| c.contains(hash))
+                    || self.bundle.as_ref().map_or(false, |b| b.contains(hash))
+            }
+            None => false,
+        }
+    }
+
     pub fn get_from_mem_cache(
         &self,
         location: &ImportLocation,
@@ -95,12 +376,95 @@ impl<'cx> ImportEnv<'cx> {
         Some(expr)
     }
 
+    pub fn get_from_semi_semantic_cache(
+        &self,
+        key: &Hash,
+    ) -> Option<Typed<'cx>> {
+        if !self.semi_semantic_cache {
+            return None;
+        }
+        self.disk_cache
+            .as_ref()?
+            .get_semi_semantic(self.cx(), key)
+            .ok()
+    }
+
+    pub fn write_to_semi_semantic_cache(&self, key: &Hash, expr: &Typed<'cx>) {
+        if !self.semi_semantic_cache {
+            return;
+        }
+        if let Some(disk_cache) = self.disk_cache.as_ref() {
+            let _ = disk_cache.insert_semi_semantic(self.cx(), key, expr);
+        }
+    }
+
+    pub fn get_from_bundle(&self, hash: &Option<Hash>) -> Option<Typed<'cx>> {
+        let hash = hash.as_ref()?;
+        self.bundle.as_ref()?.get(self.cx(), hash)?.ok()
+    }
+
     pub fn check_hash(
         &self,
         import: ImportId<'cx>,
+        hash: &Option<Hash>,
         result: ImportResultId<'cx>,
     ) -> Result<(), Error> {
-        check_hash(self.cx(), import, result)
+        check_hash(self.cx(), import, hash, result)
+    }
+
+    /// The url under which the lockfile can pin an import of `location` with the given hash.
+    fn lockable_url<'a>(
+        &self,
+        location: &'a ImportLocation,
+        hash: &Option<Hash>,
+    ) -> Option<&'a Url> {
+        if self.lockfile.is_none()
+            || hash.is_some()
+            || location.mode() != ImportMode::Code
+        {
+            return None;
+        }
+        location.remote_url()
+    }
+
+    /// The hash that the lockfile expects for an import of `location` that has the given hash,
+    /// if any. Errors if the lockfile is only checked and has no entry for it.
+    pub fn get_locked_hash(
+        &self,
+        location: &ImportLocation,
+        hash: &Option<Hash>,
+    ) -> Result<Option<Hash>, Error> {
+        let url = match self.lockable_url(location, hash) {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let (lockfile, mode) = self.lockfile.as_ref().unwrap();
+        match (lockfile.get(url), mode) {
+            (_, LockfileMode::Refresh) => Ok(None),
+            (Some(hash), _) => Ok(Some(hash.clone())),
+            (None, LockfileMode::Update) => Ok(None),
+            (None, LockfileMode::Check) => {
+                Err(ImportError::NotLocked(url.to_string()).into())
+            }
+        }
+    }
+
+    /// Add the value of an import of `location` that has the given hash to the lockfile, if it
+    /// should be locked. Returns the hash it was locked to.
+    pub fn write_to_lockfile(
+        &mut self,
+        location: &ImportLocation,
+        hash: &Option<Hash>,
+        result: ImportResultId<'cx>,
+    ) -> Result<Option<Hash>, Error> {
+        let url = match self.lockable_url(location, hash) {
+            Some(url) => url.clone(),
+            None => return Ok(None),
+        };
+        let hash = self.cx()[result].semantic_hash(self.cx())?;
+        let (lockfile, _) = self.lockfile.as_mut().unwrap();
+        lockfile.insert(url, hash.clone());
+        Ok(Some(hash))
     }
 
     pub fn write_to_mem_cache(
@@ -124,18 +488,20 @@ impl<'cx> ImportEnv<'cx> {
         }
     }
 
-    pub fn with_cycle_detection(
+    pub fn with_cycle_detection<T>(
         &mut self,
-        location: ImportLocation,
-        do_resolve: impl FnOnce(&mut Self) -> Result<Typed<'cx>, Error>,
-    ) -> Result<Typed<'cx>, Error> {
-        if self.stack.contains(&location) {
-            return Err(
-                ImportError::ImportCycle(self.stack.clone(), location).into()
-            );
+        frame: ImportFrame,
+        do_resolve: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.stack.iter().any(|f| f.location == frame.location) {
+            return Err(ImportError::ImportCycle(
+                self.stack.clone(),
+                frame.location,
+            )
+            .into());
         }
         // Push the current location on the stack
-        self.stack.push(location);
+        self.stack.push(frame);
         // Resolve the import recursively
         // WARNING: do not propagate errors here or the stack will get messed up.
         let result = do_resolve(self);
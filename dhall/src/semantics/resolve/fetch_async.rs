@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use futures_util::future::join_all;
+use url::Url;
+
+use crate::error::{Error, ImportError};
+use crate::semantics::{Headers, ImportEnv, RemoteFile, RemoteFiles};
+use crate::{Ctxt, Parsed, Resolved};
+
+impl Parsed {
+    /// Parse an expression with `parse` and resolve its imports like `resolve_with_env`, without
+    /// blocking the async runtime, then pass the result to `f`, e.g. to typecheck it.
+    ///
+    /// Remote files are downloaded concurrently without blocking. Everything else, i.e. parsing,
+    /// reading local files, resolving, writing to the cache and `f` itself, runs on tokio's
+    /// blocking thread pool. An expression and its context can't be sent to another thread, so
+    /// `parse` is called and a new `Ctxt` is created there each time. `make_env` creates the
+    /// environment to resolve with, so that the import policy, caches etc. are taken into
+    /// account. This must be called from within a tokio runtime.
+    ///
+    /// Which files are needed can depend on the contents of other files, e.g. for relative
+    /// imports or `?` alternatives. So the imports are first gone through repeatedly with the
+    /// files downloaded so far, only parsing the imported files, and the missing ones are
+    /// downloaded, until nothing is missing. Then the expression is resolved once. Since a type
+    /// error can also select the other side of a `?`, that can still need more files, in which
+    /// case they are downloaded and the expression is resolved again.
+    ///
+    /// The returned future is `Send`.
+    pub async fn resolve_async<P, E, F, T>(
+        parse: P,
+        make_env: E,
+        f: F,
+    ) -> Result<T, Error>
+    where
+        P: Fn() -> Result<Parsed, Error> + Send + Sync + 'static,
+        E: for<'cx> Fn(Ctxt<'cx>) -> ImportEnv<'cx> + Send + Sync + 'static,
+        F: for<'cx> FnOnce(Ctxt<'cx>, Resolved<'cx>) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let parse = Arc::new(parse);
+        let make_env = Arc::new(make_env);
+        let mut files = RemoteFiles::new();
+        loop {
+            let (parse, make_env) = (parse.clone(), make_env.clone());
+            let (res, files_back, missing) = spawn_blocking(move || {
+                Ctxt::with_new(|cx| {
+                    let mut env =
+                        make_env(cx).with_remote_files(files).discovering();
+                    let res = parse()
+                        .and_then(|parsed| parsed.resolve_with_env(&mut env));
+                    let missing = env.missing_remote_files().to_vec();
+                    (res.map(|_| ()), env.into_remote_files(), missing)
+                })
+            })
+            .await;
+            files = files_back;
+            if missing.is_empty() {
+                // Downloading more files can't fix this error.
+                res?;
+                break;
+            }
+            download_missing(&mut files, missing).await;
+        }
+
+        let mut f = f;
+        loop {
+            let (parse, make_env) = (parse.clone(), make_env.clone());
+            // `f` only runs once nothing is missing; otherwise we get it back.
+            let round = spawn_blocking(move || {
+                Ctxt::with_new(|cx| {
+                    let mut env = make_env(cx).with_remote_files(files);
+                    let res = parse()
+                        .and_then(|parsed| parsed.resolve_with_env(&mut env));
+                    let missing = env.missing_remote_files().to_vec();
+                    let files = env.into_remote_files();
+                    if missing.is_empty() {
+                        Ok(res.map(|resolved| f(cx, resolved)))
+                    } else {
+                        Err((f, files, missing))
+                    }
+                })
+            })
+            .await;
+            match round {
+                Ok(res) => return res,
+                Err((f_back, files_back, missing)) => {
+                    f = f_back;
+                    files = files_back;
+                    download_missing(&mut files, missing).await;
+                }
+            }
+        }
+    }
+}
+
+/// Run blocking work on tokio's blocking thread pool, forwarding panics.
+async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Download the missing files concurrently, recording failures too.
+async fn download_missing(
+    files: &mut RemoteFiles,
+    missing: Vec<(Url, Headers)>,
+) {
+    let downloads = missing.iter().map(|(url, headers)| {
+        download_http_text_async(url.clone(), headers.clone())
+    });
+    let results = join_all(downloads).await;
+    for ((url, headers), res) in missing.into_iter().zip(results) {
+        files.insert(url, headers, res);
+    }
+}
+
+async fn download_http_text_async(
+    url: Url,
+    headers: Headers,
+) -> Result<RemoteFile, Error> {
+    let name = url.to_string();
+    let mut request = reqwest::Client::new().get(url);
+    for (name, value) in &headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().await.map_err(|e| ImportError::Network {
+        url: name.clone(),
+        error: e.to_string(),
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ImportError::HttpStatus {
+            url: name,
+            status: status.as_u16(),
+        }
+        .into());
+    }
+    let allow_origin = response
+        .headers()
+        .get(reqwest::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    Ok(RemoteFile {
+        text: response.text().await.map_err(|e| {
+            ImportError::ResponseDecode {
+                url: name,
+                error: e.to_string(),
+            }
+        })?,
+        allow_origin,
+    })
+}
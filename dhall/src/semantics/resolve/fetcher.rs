@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use url::Url;
+
+use crate::error::{Error, ErrorKind, ImportError};
+use crate::semantics::Headers;
+
+/// Controls how the contents of imports are obtained.
+///
+/// The resolver asks the fetcher for the raw text behind each import; parsing and chaining of
+/// relative imports is done by the resolver, both for code imports and for `as Text` imports.
+/// Implement this to serve imports from somewhere other than the filesystem, the environment or
+/// the network, e.g. an in-memory map in tests. Wrap the fetcher in an `Rc` or `Arc` to keep
+/// access to it after resolution.
+pub trait ImportFetcher {
+    /// Read the contents of a local file.
+    fn fetch_local(&self, path: &Path) -> Result<String, Error>;
+    /// Download the contents of a remote file, sending the provided HTTP headers.
+    fn fetch_remote(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error>;
+    /// Read the value of an environment variable.
+    fn fetch_env(&self, name: &str) -> Result<String, Error>;
+    /// Download several remote files, each with its own headers. The resolver uses this for
+    /// imports that don't depend on each other, so they may be downloaded concurrently. By
+    /// default they are downloaded one after the other.
+    fn fetch_remote_many(
+        &self,
+        requests: &[(Url, Headers)],
+    ) -> Vec<Result<RemoteFile, Error>> {
+        requests
+            .iter()
+            .map(|(url, headers)| self.fetch_remote(url, headers))
+            .collect()
+    }
+    /// Whether hashed imports may be read from and added to the default on-disk cache. A
+    /// fetcher that should be the only source of imports returns `false`, and then
+    /// `ImportEnv::with_fetcher` turns the cache off. Defaults to `true`.
+    fn use_default_cache(&self) -> bool {
+        true
+    }
+}
+
+/// The contents of a downloaded remote file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteFile {
+    pub text: String,
+    /// The value of the `Access-Control-Allow-Origin` response header, if any. Used to decide
+    /// whether a remote file may be imported from a different origin.
+    pub allow_origin: Option<String>,
+}
+
+impl From<String> for RemoteFile {
+    fn from(text: String) -> Self {
+        RemoteFile {
+            text,
+            allow_origin: None,
+        }
+    }
+}
+
+/// Remote files that were downloaded ahead of resolution, along with the downloads that failed.
+///
+/// See `ImportEnv::with_remote_files`.
+#[derive(Debug, Default)]
+pub struct RemoteFiles {
+    files: HashMap<(Url, Headers), Result<RemoteFile, Error>>,
+}
+
+impl RemoteFiles {
+    pub fn new() -> Self {
+        RemoteFiles::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        url: Url,
+        headers: Headers,
+        file: Result<RemoteFile, Error>,
+    ) {
+        self.files.insert((url, headers), file);
+    }
+
+    /// The result of downloading this url with these headers, if it was downloaded.
+    pub fn get(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Option<Result<RemoteFile, Error>> {
+        let res = self.files.get(&(url.clone(), headers.to_vec()))?;
+        Some(match res {
+            Ok(file) => Ok(file.clone()),
+            Err(e) => Err(replay_error(url, e)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// Make a copy of an error from downloading `url`.
+fn replay_error(url: &Url, e: &Error) -> Error {
+    let e = match e.kind() {
+        ErrorKind::Resolve(ImportError::Network { url, error }) => {
+            ImportError::Network {
+                url: url.clone(),
+                error: error.clone(),
+            }
+        }
+        ErrorKind::Resolve(ImportError::HttpStatus { url, status }) => {
+            ImportError::HttpStatus {
+                url: url.clone(),
+                status: *status,
+            }
+        }
+        ErrorKind::Resolve(ImportError::ResponseDecode { url, error }) => {
+            ImportError::ResponseDecode {
+                url: url.clone(),
+                error: error.clone(),
+            }
+        }
+        ErrorKind::Resolve(ImportError::RemoteImportsDisabled(url)) => {
+            ImportError::RemoteImportsDisabled(url.clone())
+        }
+        _ => ImportError::Network {
+            url: url.to_string(),
+            error: e.to_string(),
+        },
+    };
+    e.into()
+}
+
+/// The fetcher used by default: reads from the filesystem, the process environment and, if the
+/// `reqwest` feature is enabled, the network. Independent remote imports are downloaded
+/// concurrently, up to `MAX_CONCURRENT_DOWNLOADS` at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFetcher;
+
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+impl ImportFetcher for DefaultFetcher {
+    fn fetch_local(&self, path: &Path) -> Result<String, Error> {
+        Ok(std::fs::read_to_string(path)?)
+    }
+    fn fetch_remote(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error> {
+        download_http_text(url.clone(), headers)
+    }
+    fn fetch_env(&self, name: &str) -> Result<String, Error> {
+        match env::var(name) {
+            Ok(val) => Ok(val),
+            Err(_) => Err(ImportError::MissingEnvVar.into()),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn fetch_remote_many(
+        &self,
+        requests: &[(Url, Headers)],
+    ) -> Vec<Result<RemoteFile, Error>> {
+        let mut results = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_CONCURRENT_DOWNLOADS) {
+            let threads: Vec<_> = chunk
+                .iter()
+                .cloned()
+                .map(|(url, headers)| {
+                    let name = url.to_string();
+                    let thread = std::thread::spawn(move || {
+                        download_http_text(url, &headers)
+                    });
+                    (name, thread)
+                })
+                .collect();
+            results.extend(threads.into_iter().map(|(url, thread)| {
+                thread.join().unwrap_or_else(|_| {
+                    Err(ImportError::Network {
+                        url,
+                        error: "the download panicked".to_owned(),
+                    }
+                    .into())
+                })
+            }));
+        }
+        results
+    }
+}
+
+/// A fetcher that downloads remote files with a function, given the url and the headers to
+/// send, and otherwise behaves like `DefaultFetcher`.
+///
+/// This is how remote imports work on wasm, where this crate can't access the network: the
+/// embedder provides a function that fetches the file, e.g. with a synchronous request in the
+/// browser. Pass it to `ImportEnv::with_fetcher`.
+#[derive(Clone, Copy)]
+pub struct FnFetcher<F> {
+    fetch_remote: F,
+}
+
+impl<F> FnFetcher<F>
+where
+    F: Fn(&Url, &[(String, String)]) -> Result<RemoteFile, Error>,
+{
+    pub fn new(fetch_remote: F) -> Self {
+        FnFetcher { fetch_remote }
+    }
+}
+
+impl<F> fmt::Debug for FnFetcher<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnFetcher").finish()
+    }
+}
+
+impl<F> ImportFetcher for FnFetcher<F>
+where
+    F: Fn(&Url, &[(String, String)]) -> Result<RemoteFile, Error>,
+{
+    fn fetch_local(&self, path: &Path) -> Result<String, Error> {
+        DefaultFetcher.fetch_local(path)
+    }
+    fn fetch_remote(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error> {
+        (self.fetch_remote)(url, headers)
+    }
+    fn fetch_env(&self, name: &str) -> Result<String, Error> {
+        DefaultFetcher.fetch_env(name)
+    }
+}
+
+macro_rules! forward_fetcher_impl {
+    ($ty:ty) => {
+        impl<F: ImportFetcher + ?Sized> ImportFetcher for $ty {
+            fn fetch_local(&self, path: &Path) -> Result<String, Error> {
+                (**self).fetch_local(path)
+            }
+            fn fetch_remote(
+                &self,
+                url: &Url,
+                headers: &[(String, String)],
+            ) -> Result<RemoteFile, Error> {
+                (**self).fetch_remote(url, headers)
+            }
+            fn fetch_env(&self, name: &str) -> Result<String, Error> {
+                (**self).fetch_env(name)
+            }
+            fn fetch_remote_many(
+                &self,
+                requests: &[(Url, Headers)],
+            ) -> Vec<Result<RemoteFile, Error>> {
+                (**self).fetch_remote_many(requests)
+            }
+            fn use_default_cache(&self) -> bool {
+                (**self).use_default_cache()
+            }
+        }
+    };
+}
+
+forward_fetcher_impl!(Box<F>);
+forward_fetcher_impl!(Rc<F>);
+forward_fetcher_impl!(Arc<F>);
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "reqwest"))]
+pub(crate) fn download_http_text(
+    url: Url,
+    headers: &[(String, String)],
+) -> Result<RemoteFile, Error> {
+    let name = url.to_string();
+    let mut request = reqwest::blocking::Client::new().get(url);
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let response = request.send().map_err(|e| ImportError::Network {
+        url: name.clone(),
+        error: e.to_string(),
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(ImportError::HttpStatus {
+            url: name,
+            status: status.as_u16(),
+        }
+        .into());
+    }
+    let allow_origin = response
+        .headers()
+        .get(reqwest::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    Ok(RemoteFile {
+        text: response.text().map_err(|e| ImportError::ResponseDecode {
+            url: name,
+            error: e.to_string(),
+        })?,
+        allow_origin,
+    })
+}
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "reqwest")))]
+pub(crate) fn download_http_text(
+    url: Url,
+    _headers: &[(String, String)],
+) -> Result<RemoteFile, Error> {
+    Err(ImportError::RemoteImportsDisabled(url.to_string()).into())
+}
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn download_http_text(
+    url: Url,
+    _headers: &[(String, String)],
+) -> Result<RemoteFile, Error> {
+    // Reqwest needs proper async support to work on wasm. Embedders can use a `FnFetcher`
+    // instead.
+    Err(ImportError::RemoteImportsDisabled(url.to_string()).into())
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use crate::ctxt::StoredImport;
+use crate::error::Error;
+use crate::semantics::resolve::resolve::{
+    fetch_import, resolve_collecting_nodes,
+};
+use crate::semantics::{Hir, HirKind, ImportEnv, ImportNode};
+use crate::syntax::{
+    parse_expr, Expr, ExprKind, Hash, Import, ImportMode, ImportTarget, Span,
+};
+use crate::{Ctxt, Parsed};
+
+/// Controls which imports `Parsed::freeze` adds integrity checks to. By default only remote
+/// imports are frozen, and their values are added to the cache.
+///
+/// # Example
+///
+/// ```
+/// use dhall::semantics::FreezeOptions;
+///
+/// // Also freeze local files and environment variables.
+/// let options = FreezeOptions::default().all_imports(true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreezeOptions {
+    all_imports: bool,
+    cache: bool,
+}
+
+impl Default for FreezeOptions {
+    fn default() -> Self {
+        FreezeOptions {
+            all_imports: false,
+            cache: true,
+        }
+    }
+}
+
+impl FreezeOptions {
+    /// Sets whether to freeze local and environment variable imports as well as remote ones.
+    pub fn all_imports(self, all: bool) -> Self {
+        FreezeOptions {
+            all_imports: all,
+            ..self
+        }
+    }
+    /// Sets whether to add the values of the frozen imports to the on-disk cache of the
+    /// environment, so that they don't need to be fetched again.
+    pub fn cache(self, cache: bool) -> Self {
+        FreezeOptions { cache, ..self }
+    }
+
+    fn should_freeze<SE>(&self, import: &Import<SE>) -> bool {
+        if import.hash.is_some() || import.mode != ImportMode::Code {
+            return false;
+        }
+        match import.location {
+            ImportTarget::Remote(_) => true,
+            ImportTarget::Local(..) | ImportTarget::Env(_) => self.all_imports,
+            ImportTarget::Missing => false,
+        }
+    }
+}
+
+impl Parsed {
+    /// Add `sha256` integrity checks to the imports of this expression, by resolving it. Only
+    /// imports of dhall code that don't already have a hash are frozen. An import on either side
+    /// of a `?` that fails to resolve is left as is.
+    ///
+    /// The result has the same location as `self`, so it can be resolved the same way.
+    pub fn freeze(
+        &self,
+        cx: Ctxt<'_>,
+        options: FreezeOptions,
+    ) -> Result<Parsed, Error> {
+        self.freeze_with_env(&mut ImportEnv::new(cx), options)
+    }
+
+    /// Like `freeze`, but with a custom import environment.
+    pub fn freeze_with_env(
+        &self,
+        env: &mut ImportEnv<'_>,
+        options: FreezeOptions,
+    ) -> Result<Parsed, Error> {
+        let parsed = self.with_source_spans()?;
+        let hashes = hash_imports(env, options, &parsed)?;
+        let expr = freeze_expr(options, &hashes, &parsed.0);
+        Ok(Parsed(expr, parsed.1))
+    }
+
+    /// Like `freeze`, but returns the text this expression was parsed from with the hashes
+    /// inserted after the imports, so that comments and formatting are kept. If the expression
+    /// wasn't parsed from text, the frozen expression is printed instead.
+    pub fn freeze_source(
+        &self,
+        cx: Ctxt<'_>,
+        options: FreezeOptions,
+    ) -> Result<String, Error> {
+        self.freeze_source_with_env(&mut ImportEnv::new(cx), options)
+    }
+
+    /// Like `freeze_source`, but with a custom import environment.
+    pub fn freeze_source_with_env(
+        &self,
+        env: &mut ImportEnv<'_>,
+        options: FreezeOptions,
+    ) -> Result<String, Error> {
+        let input = match self.0.span() {
+            Span::Parsed(span) => span.to_input(),
+            _ => {
+                let frozen = self.freeze_with_env(env, options)?;
+                return Ok(format!("{}\n", frozen.to_expr()));
+            }
+        };
+        let hashes = hash_imports(env, options, self)?;
+        let mut insertions = Vec::new();
+        collect_insertions(options, &hashes, &self.0, &mut insertions);
+        // Insertions at the same place stay in the order they were collected in.
+        insertions.sort_by_key(|(at, _)| *at);
+        let mut source = String::with_capacity(input.len());
+        let mut copied = 0;
+        for (at, text) in insertions {
+            source.push_str(&input[copied..at]);
+            source.push_str(&text);
+            copied = at;
+        }
+        source.push_str(&input[copied..]);
+        Ok(source)
+    }
+
+    /// Imports are matched with their resolution by their location in the source, so binary
+    /// expressions are printed and parsed again.
+    fn with_source_spans(&self) -> Result<Parsed, Error> {
+        Ok(match self.0.span() {
+            Span::Parsed(_) => self.clone(),
+            _ => Parsed(parse_expr(&self.0.to_string())?, self.1.clone()),
+        })
+    }
+}
+
+/// The hashes of the imports to freeze, by location in the source.
+type ImportHashes = HashMap<(usize, usize), Hash>;
+
+/// Resolve the expression once, and compute the hashes of the imports to freeze.
+fn hash_imports(
+    env: &mut ImportEnv<'_>,
+    options: FreezeOptions,
+    parsed: &Parsed,
+) -> Result<ImportHashes, Error> {
+    let (_, nodes) = resolve_collecting_nodes(env, parsed.clone())?;
+    let mut hashes = HashMap::new();
+    hash_nodes(env, options, &nodes, &mut hashes)?;
+    Ok(hashes)
+}
+
+/// Compute the hashes of the imports in `nodes` that should be frozen. Resolution only went
+/// through one side of each `?`, so the imports it didn't need are resolved here; those that fail
+/// are left without a hash.
+fn hash_nodes<'cx>(
+    env: &mut ImportEnv<'cx>,
+    options: FreezeOptions,
+    nodes: &[ImportNode<'cx>],
+    hashes: &mut ImportHashes,
+) -> Result<(), Error> {
+    let cx = env.cx();
+    for &node in nodes {
+        let import_id = match node {
+            ImportNode::Import(import_id) => import_id,
+            ImportNode::Alternative(alt) => {
+                hash_nodes(env, options, &cx[alt].left_imports, hashes)?;
+                hash_nodes(env, options, &cx[alt].right_imports, hashes)?;
+                continue;
+            }
+        };
+        let import = &cx[import_id];
+        // Evaluating the headers requires their imports.
+        if import.get_resultid().is_none() && headers_resolved(cx, import) {
+            if let Ok(Some(res_id)) = fetch_import(env, import_id) {
+                import.set_resultid(res_id);
+            }
+        }
+        let (typed, range) =
+            match (import.get_result(), import.span.byte_range()) {
+                (Some(typed), Some(range))
+                    if options.should_freeze(&import.import) =>
+                {
+                    (typed, range)
+                }
+                _ => continue,
+            };
+        let hash = typed.semantic_hash(cx)?;
+        if options.cache {
+            if let Some(cache) = env.disk_cache() {
+                let _ = cache.insert(cx, &hash, typed);
+            }
+        }
+        hashes.insert(range, hash);
+    }
+    Ok(())
+}
+
+fn headers_resolved<'cx>(cx: Ctxt<'cx>, import: &StoredImport<'cx>) -> bool {
+    import
+        .import
+        .traverse_ref(|headers| check_resolved(cx, headers))
+        .is_ok()
+}
+
+/// Fails if an import used by `hir` has no result.
+fn check_resolved<'cx>(cx: Ctxt<'cx>, hir: &Hir<'cx>) -> Result<(), ()> {
+    match hir.kind() {
+        HirKind::Import(import) => match cx[*import].get_resultid() {
+            Some(_) => Ok(()),
+            None => Err(()),
+        },
+        HirKind::ImportAlternative(alt, left, right) => {
+            match cx[*alt].get_selected() {
+                Some(true) => check_resolved(cx, left),
+                Some(false) => check_resolved(cx, right),
+                None => Err(()),
+            }
+        }
+        HirKind::Expr(kind) => {
+            kind.traverse_ref(|e| check_resolved(cx, e))?;
+            Ok(())
+        }
+        HirKind::Var(_) | HirKind::MissingVar(_) => Ok(()),
+    }
+}
+
+fn freeze_expr(
+    options: FreezeOptions,
+    hashes: &ImportHashes,
+    expr: &Expr,
+) -> Expr {
+    let kind = match expr.kind() {
+        ExprKind::Import(import) => {
+            let mut import =
+                import.map_ref(|headers| freeze_expr(options, hashes, headers));
+            if let Some(hash) = frozen_hash(options, hashes, expr, &import) {
+                import.hash = Some(hash.clone());
+            }
+            ExprKind::Import(import)
+        }
+        kind => kind.map_ref(|e| freeze_expr(options, hashes, e)),
+    };
+    Expr::new(kind, expr.span())
+}
+
+/// Collect the text to insert in the source, in the order `freeze_expr` would add hashes.
+fn collect_insertions(
+    options: FreezeOptions,
+    hashes: &ImportHashes,
+    expr: &Expr,
+    insertions: &mut Vec<(usize, String)>,
+) {
+    let import = match expr.kind() {
+        ExprKind::Import(import) => import,
+        kind => {
+            kind.map_ref(|e| {
+                collect_insertions(options, hashes, e, insertions)
+            });
+            return;
+        }
+    };
+    let hash = frozen_hash(options, hashes, expr, import);
+    let end = expr.span().byte_range().map(|(_, end)| end);
+    import.map_ref(|headers| {
+        // A hash after an import in the headers would apply to that import, so the headers
+        // get parentheses if they don't have some already.
+        let parens = match (headers.kind(), headers.span().byte_range()) {
+            (ExprKind::Import(_), Some((start, header_end)))
+                if hash.is_some() && Some(header_end) == end =>
+            {
+                Some((start, header_end))
+            }
+            _ => None,
+        };
+        if let Some((start, _)) = parens {
+            insertions.push((start, "(".to_owned()));
+        }
+        collect_insertions(options, hashes, headers, insertions);
+        if let Some((_, header_end)) = parens {
+            insertions.push((header_end, ")".to_owned()));
+        }
+    });
+    if let (Some(hash), Some(end)) = (hash, end) {
+        insertions.push((end, format!(" {}", hash)));
+    }
+}
+
+/// The hash to add to the import `expr`, if any.
+fn frozen_hash<'a, SE>(
+    options: FreezeOptions,
+    hashes: &'a ImportHashes,
+    expr: &Expr,
+    import: &Import<SE>,
+) -> Option<&'a Hash> {
+    if !options.should_freeze(import) {
+        return None;
+    }
+    hashes.get(&expr.span().byte_range()?)
+}
@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::semantics::{ImportLocation, ImportNode};
+use crate::syntax::{Hash, Span};
+use crate::Ctxt;
+
+/// The imports that were resolved to get an expression, e.g. to know which files a build output
+/// depends on. Returned by `Resolved::import_graph`.
+///
+/// Only the imports whose resolution was attempted are included: the right side of a `?` is
+/// skipped when the left side succeeded. Imports found in a cache by their hash aren't read, so
+/// their own imports are unknown.
+#[derive(Debug, Clone)]
+pub struct ImportGraph {
+    /// The imported locations, each listed once. The first one is the location of the root
+    /// expression.
+    pub nodes: Vec<ImportGraphNode>,
+    /// The imports, in the order they appear in the importing files.
+    pub edges: Vec<ImportEdge>,
+    /// The `?` alternatives that contain imports.
+    pub alternatives: Vec<ImportAlternativeChoice>,
+}
+
+/// A location in an `ImportGraph`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportGraphNode {
+    pub location: ImportLocation,
+    /// The hash that protects an import of this location, if any.
+    pub hash: Option<Hash>,
+    /// Whether the contents of the location were read. They aren't when the result came from a
+    /// cache, or for `as Location` imports.
+    pub read: bool,
+}
+
+/// An import of the node at index `to` in the file at index `from`.
+#[derive(Debug, Clone)]
+pub struct ImportEdge {
+    pub from: usize,
+    pub to: usize,
+    /// Where the import appears in the importing file.
+    pub span: Span,
+    /// Whether the import succeeded. Only imports on the left of a `?` can fail.
+    pub resolved: bool,
+}
+
+/// A `?` alternative in the file at index `file`, with the indices of the edges on each side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportAlternativeChoice {
+    pub file: usize,
+    pub left: Vec<usize>,
+    pub right: Vec<usize>,
+    /// `true` if the left side was selected, `false` for the right.
+    pub selected_left: bool,
+}
+
+struct GraphBuilder<'cx> {
+    cx: Ctxt<'cx>,
+    graph: ImportGraph,
+    indices: HashMap<ImportLocation, usize>,
+    /// The nodes whose imports were already visited.
+    visited: HashSet<usize>,
+}
+
+impl ImportGraph {
+    pub(crate) fn new<'cx>(
+        cx: Ctxt<'cx>,
+        location: &ImportLocation,
+        imports: &[ImportNode<'cx>],
+    ) -> Self {
+        let mut builder = GraphBuilder {
+            cx,
+            graph: ImportGraph {
+                nodes: Vec::new(),
+                edges: Vec::new(),
+                alternatives: Vec::new(),
+            },
+            indices: HashMap::new(),
+            visited: HashSet::new(),
+        };
+        let root = builder.node(location.clone(), None, true);
+        builder.visited.insert(root);
+        builder.visit(root, imports);
+        builder.graph
+    }
+
+    /// The indices of the nodes imported by the node at index `from`.
+    pub fn imports_of(&self, from: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.from == from)
+            .map(|edge| edge.to)
+    }
+
+    /// The local files that were read, including the root file. A file imported both as code
+    /// and as text is listed once.
+    pub fn read_files(&self) -> Vec<&Path> {
+        let mut files = Vec::new();
+        for node in self.nodes.iter().filter(|node| node.read) {
+            if let Some(path) = node.location.local_path() {
+                if !files.contains(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        files
+    }
+
+    /// The environment variables that were read.
+    pub fn read_env_vars(&self) -> Vec<&str> {
+        let mut vars = Vec::new();
+        for node in self.nodes.iter().filter(|node| node.read) {
+            if let Some(var) = node.location.env_var() {
+                if !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+        }
+        vars
+    }
+
+    /// A rule in the Makefile syntax that makes `target` depend on the files that were read, as
+    /// understood by make and ninja. Environment variables can't be expressed as prerequisites;
+    /// use `read_env_vars` to track them.
+    pub fn to_depfile(&self, target: &str) -> String {
+        let mut depfile = escape_make(target);
+        depfile.push(':');
+        for path in self.read_files() {
+            depfile.push_str(" \\\n  ");
+            depfile.push_str(&escape_make(&path.to_string_lossy()));
+        }
+        depfile.push('\n');
+        depfile
+    }
+}
+
+/// Escape the characters that are special in a Makefile rule.
+fn escape_make(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<'cx> GraphBuilder<'cx> {
+    /// Get the index of the node for `location`, adding it if needed.
+    fn node(
+        &mut self,
+        location: ImportLocation,
+        hash: Option<Hash>,
+        read: bool,
+    ) -> usize {
+        let nodes = &mut self.graph.nodes;
+        let i = *self.indices.entry(location.clone()).or_insert_with(|| {
+            nodes.push(ImportGraphNode {
+                location,
+                hash: None,
+                read: false,
+            });
+            nodes.len() - 1
+        });
+        let node = &mut nodes[i];
+        if node.hash.is_none() {
+            node.hash = hash;
+        }
+        node.read |= read;
+        i
+    }
+
+    /// Add the edges for `imports`, found in the file at index `file`. Returns the indices of
+    /// the new edges.
+    fn visit(
+        &mut self,
+        file: usize,
+        imports: &[ImportNode<'cx>],
+    ) -> Vec<usize> {
+        let cx = self.cx;
+        let mut edges = Vec::new();
+        for &node in imports {
+            match node {
+                ImportNode::Import(import) => {
+                    let stored = &cx[import];
+                    let location = match stored.get_location() {
+                        Some(location) => location.clone(),
+                        // Resolving this import wasn't attempted.
+                        None => continue,
+                    };
+                    let result = stored.get_resultid();
+                    let imports =
+                        result.and_then(|res| cx.get_result_imports(res));
+                    let to = self.node(
+                        location,
+                        stored.import.hash.clone(),
+                        imports.is_some(),
+                    );
+                    edges.push(self.graph.edges.len());
+                    self.graph.edges.push(ImportEdge {
+                        from: file,
+                        to,
+                        span: stored.span.clone(),
+                        resolved: result.is_some(),
+                    });
+                    if let Some(imports) = imports {
+                        if self.visited.insert(to) {
+                            self.visit(to, imports);
+                        }
+                    }
+                }
+                ImportNode::Alternative(alt) => {
+                    let alt = &cx[alt];
+                    let left = self.visit(file, &alt.left_imports);
+                    let right = self.visit(file, &alt.right_imports);
+                    edges.extend(&left);
+                    edges.extend(&right);
+                    // Alternatives inside a side that failed may not have been decided.
+                    if let Some(selected_left) = alt.get_selected() {
+                        if !left.is_empty() || !right.is_empty() {
+                            self.graph.alternatives.push(
+                                ImportAlternativeChoice {
+                                    file,
+                                    left,
+                                    right,
+                                    selected_left,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        edges
+    }
+}
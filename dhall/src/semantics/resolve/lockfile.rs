@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use url::Url;
+
+use crate::error::{Error, ImportError};
+use crate::syntax::Hash;
+
+/// Pins the contents of remote imports without editing the files that import them, by mapping
+/// urls to the hash of their value. The values themselves are stored in the on-disk cache.
+///
+/// Use it with `ImportEnv::with_lockfile`. Only remote imports of dhall code that don't have a
+/// hash of their own are locked.
+///
+/// The file lists one import per line, as the url followed by the hash. Empty lines and lines
+/// starting with `#` are ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// use dhall::semantics::{ImportEnv, Lockfile, LockfileMode};
+/// use dhall::{Ctxt, Parsed};
+/// # fn main() -> Result<(), dhall::error::Error> {
+///
+/// let lockfile = Lockfile::from_file(Lockfile::FILENAME)?;
+/// let lockfile = Ctxt::with_new(|cx| -> Result<_, dhall::error::Error> {
+///     let mut env =
+///         ImportEnv::new(cx).with_lockfile(lockfile, LockfileMode::Update);
+///     Parsed::parse_str("https://example.com/package.dhall")?
+///         .resolve_with_env(&mut env)?;
+///     Ok(env.into_lockfile().unwrap())
+/// })?;
+/// lockfile.write(Lockfile::FILENAME)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    entries: BTreeMap<Url, Hash>,
+}
+
+/// How the lockfile is used during resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfileMode {
+    /// Verify the locked imports, and add the other ones to the lockfile.
+    Update,
+    /// Verify the locked imports, and reject the other ones.
+    Check,
+    /// Lock every import to its current contents, replacing the existing entries.
+    Refresh,
+}
+
+impl Lockfile {
+    /// The conventional name of the lockfile.
+    pub const FILENAME: &'static str = "dhall.lock";
+
+    pub fn new() -> Self {
+        Lockfile::default()
+    }
+
+    /// Read a lockfile. A missing file gives an empty lockfile.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => s.parse(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Lockfile::new())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.to_string())?)
+    }
+
+    pub fn get(&self, url: &Url) -> Option<&Hash> {
+        self.entries.get(url)
+    }
+
+    /// Lock `url` to `hash`. Returns the hash it was previously locked to, if any.
+    pub fn insert(&mut self, url: Url, hash: Hash) -> Option<Hash> {
+        self.entries.insert(url, hash)
+    }
+
+    pub fn remove(&mut self, url: &Url) -> Option<Hash> {
+        self.entries.remove(url)
+    }
+
+    /// Iterate over the entries, sorted by url.
+    pub fn iter(&self) -> impl Iterator<Item = (&Url, &Hash)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl FromStr for Lockfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut lockfile = Lockfile::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                ImportError::InvalidLockfile(format!(
+                    "line {}: expected a url and a hash",
+                    i + 1
+                ))
+            };
+            let mut words = line.split_whitespace();
+            let (url, hash) = match (words.next(), words.next(), words.next()) {
+                (Some(url), Some(hash), None) => (url, hash),
+                _ => return Err(invalid().into()),
+            };
+            let hash = Some(hash)
+                .filter(|hash| hash.starts_with("sha256:"))
+                .and_then(|hash| hex::decode(&hash["sha256:".len()..]).ok())
+                .filter(|hash| hash.len() == 32)
+                .ok_or_else(invalid)?;
+            lockfile.insert(Url::parse(url)?, Hash::SHA256(hash.into()));
+        }
+        Ok(lockfile)
+    }
+}
+
+impl fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# Hashes of the remote imports, managed by dhall.")?;
+        for (url, hash) in &self.entries {
+            writeln!(f, "{} {}", url, hash)?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,24 @@
+pub mod bundle;
 pub mod cache;
 pub mod env;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub mod fetch_async;
+pub mod fetcher;
+pub mod freeze;
+pub mod graph;
 pub mod hir;
+pub mod lockfile;
+pub mod policy;
 pub mod resolve;
+pub mod vfs;
+pub use bundle::*;
 pub use cache::*;
 pub use env::*;
+pub use fetcher::*;
+pub use freeze::*;
+pub use graph::*;
 pub use hir::*;
+pub use lockfile::*;
+pub use policy::*;
 pub use resolve::*;
+pub use vfs::*;
@@ -0,0 +1,184 @@
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+use crate::error::{Error, ImportError};
+use crate::semantics::{ImportLocation, ImportLocationKind};
+
+/// Restricts which imports may be resolved. By default everything is allowed.
+///
+/// This only applies to imports; the file that resolution starts from is always read. `as
+/// Location` imports are always allowed since they don't access anything.
+///
+/// # Example
+///
+/// ```
+/// use dhall::semantics::ImportPolicy;
+///
+/// // Only allow local imports below `./config`, and the `HOME` env variable.
+/// let policy = ImportPolicy::default()
+///     .remote(false)
+///     .local_root("./config")
+///     .allowed_env_vars(vec!["HOME"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportPolicy {
+    allow_local: bool,
+    allow_remote: bool,
+    allow_env: bool,
+    local_root: Option<PathBuf>,
+    allowed_hosts: Option<Vec<String>>,
+    allowed_env_vars: Option<Vec<String>>,
+}
+
+impl Default for ImportPolicy {
+    fn default() -> Self {
+        ImportPolicy {
+            allow_local: true,
+            allow_remote: true,
+            allow_env: true,
+            local_root: None,
+            allowed_hosts: None,
+            allowed_env_vars: None,
+        }
+    }
+}
+
+impl ImportPolicy {
+    /// A policy that rejects every import.
+    pub fn deny_all() -> Self {
+        ImportPolicy::default()
+            .local(false)
+            .remote(false)
+            .env(false)
+    }
+
+    /// Sets whether to allow local file imports.
+    pub fn local(self, allow: bool) -> Self {
+        ImportPolicy {
+            allow_local: allow,
+            ..self
+        }
+    }
+    /// Sets whether to allow remote imports.
+    pub fn remote(self, allow: bool) -> Self {
+        ImportPolicy {
+            allow_remote: allow,
+            ..self
+        }
+    }
+    /// Sets whether to allow environment variable imports.
+    pub fn env(self, allow: bool) -> Self {
+        ImportPolicy {
+            allow_env: allow,
+            ..self
+        }
+    }
+    /// Only allow local imports of files inside `root`. Relative paths are taken relative to the
+    /// current directory. Paths are compared after lexically resolving `.` and `..`, then
+    /// following symlinks, so a link inside `root` that points outside of it is rejected. Imports
+    /// relative to the home directory are rejected.
+    pub fn local_root(self, root: impl AsRef<Path>) -> Self {
+        ImportPolicy {
+            local_root: Some(root.as_ref().to_owned()),
+            ..self
+        }
+    }
+    /// Only allow remote imports from these hosts.
+    pub fn allowed_hosts<S: ToString>(
+        self,
+        hosts: impl IntoIterator<Item = S>,
+    ) -> Self {
+        ImportPolicy {
+            allowed_hosts: Some(
+                hosts.into_iter().map(|h| h.to_string()).collect(),
+            ),
+            ..self
+        }
+    }
+    /// Only allow imports of these environment variables.
+    pub fn allowed_env_vars<S: ToString>(
+        self,
+        vars: impl IntoIterator<Item = S>,
+    ) -> Self {
+        ImportPolicy {
+            allowed_env_vars: Some(
+                vars.into_iter().map(|v| v.to_string()).collect(),
+            ),
+            ..self
+        }
+    }
+
+    /// Errors if the policy forbids fetching `location`.
+    pub fn check(&self, location: &ImportLocation) -> Result<(), Error> {
+        if !location.is_fetched() || self.allows(location.kind())? {
+            Ok(())
+        } else {
+            Err(ImportError::Forbidden(location.clone()).into())
+        }
+    }
+
+    fn allows(&self, kind: &ImportLocationKind) -> Result<bool, Error> {
+        Ok(match kind {
+            ImportLocationKind::Local(path) => {
+                self.allow_local
+                    && match &self.local_root {
+                        Some(root) => is_below(path, root)?,
+                        None => true,
+                    }
+            }
+            ImportLocationKind::Remote(url, _) => {
+                self.allow_remote
+                    && match &self.allowed_hosts {
+                        Some(hosts) => host_allowed(url, hosts),
+                        None => true,
+                    }
+            }
+            ImportLocationKind::Env(name) => {
+                self.allow_env
+                    && match &self.allowed_env_vars {
+                        Some(vars) => vars.iter().any(|v| v == name),
+                        None => true,
+                    }
+            }
+            ImportLocationKind::Missing | ImportLocationKind::NoImport => true,
+        })
+    }
+}
+
+fn host_allowed(url: &Url, hosts: &[String]) -> bool {
+    match url.host_str() {
+        Some(host) => hosts.iter().any(|h| h.eq_ignore_ascii_case(host)),
+        None => false,
+    }
+}
+
+/// Whether `path` is inside `root`, after making both absolute, removing `.` and `..` components
+/// and following symlinks.
+fn is_below(path: &Path, root: &Path) -> Result<bool, Error> {
+    if path.starts_with("~") {
+        return Ok(false);
+    }
+    Ok(normalize(path)?.starts_with(normalize(root)?))
+}
+
+fn normalize(path: &Path) -> Result<PathBuf, Error> {
+    let path = std::env::current_dir()?.join(path);
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    // Follow symlinks in the part of the path that exists. The rest can't be a link.
+    for ancestor in normalized.ancestors() {
+        if let Ok(canonical) = ancestor.canonicalize() {
+            let rest = normalized.strip_prefix(ancestor).unwrap();
+            return Ok(canonical.join(rest));
+        }
+    }
+    Ok(normalized)
+}
@@ -1,35 +1,40 @@
 use itertools::Itertools;
 use std::borrow::Cow;
-use std::collections::BTreeMap;
-use std::env;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use url::Url;
 
 use crate::builtins::Builtin;
 use crate::error::ErrorBuilder;
-use crate::error::{Error, ImportError};
+use crate::error::{Error, FailedAlternative, ImportError};
 use crate::operations::{BinOp, OpKind};
-use crate::semantics::{mkerr, Hir, HirKind, ImportEnv, NameEnv, Type};
+use crate::semantics::{
+    mkerr, typecheck_with, Hir, HirKind, ImportEnv, ImportFetcher, ImportFrame,
+    NameEnv, Nir, NirKind, Type,
+};
+use crate::standard;
 use crate::syntax;
 use crate::syntax::{
-    Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode, ImportTarget, Span,
-    UnspannedExpr, URL,
+    binary, parse_expr, Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode,
+    ImportTarget, Label, Span, UnspannedExpr, URL,
 };
 use crate::{
     Ctxt, ImportAlternativeId, ImportId, ImportResultId, Parsed, Resolved,
-    Typed,
+    ResultImports, Typed,
 };
 
-// TODO: evaluate import headers
-pub type Import = syntax::Import<()>;
+pub type Import<'cx> = syntax::Import<Hir<'cx>>;
+
+/// HTTP headers to send with a remote import, as `(name, value)` pairs.
+pub type Headers = Vec<(String, String)>;
 
 /// The location of some data, usually some dhall code.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum ImportLocationKind {
+pub(crate) enum ImportLocationKind {
     /// Local file
     Local(PathBuf),
-    /// Remote file
-    Remote(Url),
+    /// Remote file, with the headers to send when fetching it
+    Remote(Url, Headers),
     /// Environment variable
     Env(String),
     /// Data without a location; chaining will start from current directory.
@@ -93,7 +98,7 @@ impl ImportLocationKind {
                     Some(prefix.to_string()).into_iter().chain(path).collect();
                 ImportLocationKind::Local(path)
             }
-            ImportLocationKind::Remote(url) => {
+            ImportLocationKind::Remote(url, headers) => {
                 let mut url = url.clone();
                 match prefix {
                     FilePrefix::Here => {}
@@ -104,40 +109,71 @@ impl ImportLocationKind {
                     FilePrefix::Home => panic!("error"),
                 }
                 url = url.join(&path.file_path.join("/"))?;
-                ImportLocationKind::Remote(url)
+                ImportLocationKind::Remote(url, headers.clone())
             }
             ImportLocationKind::NoImport => unreachable!(),
         })
     }
 
-    fn fetch_dhall(&self) -> Result<Parsed, Error> {
-        Ok(match self {
-            ImportLocationKind::Local(path) => Parsed::parse_file(path)?,
-            ImportLocationKind::Remote(url) => {
-                Parsed::parse_remote(url.clone())?
+    fn fetch_dhall(
+        &self,
+        env: &mut ImportEnv<'_>,
+        cors_origin: Option<&str>,
+    ) -> Result<Parsed, Error> {
+        let text = self.fetch_text(env, cors_origin)?;
+        let expr = parse_expr(&text)?;
+        let root = match self {
+            ImportLocationKind::Local(path) => {
+                ImportLocation::local_dhall_code(path.clone())
             }
-            ImportLocationKind::Env(var_name) => {
-                let val = match env::var(var_name) {
-                    Ok(val) => val,
-                    Err(_) => return Err(ImportError::MissingEnvVar.into()),
-                };
-                Parsed::parse_str(&val)?
+            ImportLocationKind::Remote(url, headers) => ImportLocation {
+                kind: ImportLocationKind::Remote(url.clone(), headers.clone()),
+                mode: ImportMode::Code,
+            },
+            ImportLocationKind::Env(_) => {
+                ImportLocation::dhall_code_of_unknown_origin()
             }
-            ImportLocationKind::Missing => {
-                return Err(ImportError::Missing.into())
+            ImportLocationKind::Missing | ImportLocationKind::NoImport => {
+                unreachable!()
             }
-            ImportLocationKind::NoImport => unreachable!(),
-        })
+        };
+        Ok(Parsed(expr, root))
     }
 
-    fn fetch_text(&self) -> Result<String, Error> {
+    /// If `cors_origin` is set, the file is being imported from a remote file with that origin,
+    /// and the server must explicitly allow it.
+    fn fetch_text(
+        &self,
+        env: &mut ImportEnv<'_>,
+        cors_origin: Option<&str>,
+    ) -> Result<String, Error> {
         Ok(match self {
-            ImportLocationKind::Local(path) => std::fs::read_to_string(path)?,
-            ImportLocationKind::Remote(url) => download_http_text(url.clone())?,
-            ImportLocationKind::Env(var_name) => match env::var(var_name) {
-                Ok(val) => val,
-                Err(_) => return Err(ImportError::MissingEnvVar.into()),
-            },
+            ImportLocationKind::Local(path) => {
+                env.fetcher().fetch_local(path)?
+            }
+            ImportLocationKind::Remote(url, headers) => {
+                let file = env.fetch_remote(url, headers)?;
+                if let Some(origin) = cors_origin {
+                    let allowed = match &file.allow_origin {
+                        Some(allow) => {
+                            let allow = allow.trim();
+                            allow == "*" || allow == origin
+                        }
+                        None => false,
+                    };
+                    if !allowed {
+                        return Err(ImportError::CorsCheck {
+                            origin: origin.to_owned(),
+                            allow_origin: file.allow_origin,
+                        }
+                        .into());
+                    }
+                }
+                file.text
+            }
+            ImportLocationKind::Env(var_name) => {
+                env.fetcher().fetch_env(var_name)?
+            }
             ImportLocationKind::Missing => {
                 return Err(ImportError::Missing.into())
             }
@@ -150,7 +186,7 @@ impl ImportLocationKind {
             ImportLocationKind::Local(path) => {
                 ("Local", Some(path.to_string_lossy().into_owned()))
             }
-            ImportLocationKind::Remote(url) => {
+            ImportLocationKind::Remote(url, _) => {
                 ("Remote", Some(url.to_string()))
             }
             ImportLocationKind::Env(name) => {
@@ -173,6 +209,23 @@ impl ImportLocationKind {
     }
 }
 
+impl std::fmt::Display for ImportLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.kind {
+            ImportLocationKind::Local(path) => write!(f, "{}", path.display())?,
+            ImportLocationKind::Remote(url, _) => write!(f, "{}", url)?,
+            ImportLocationKind::Env(name) => write!(f, "env:{}", name)?,
+            ImportLocationKind::Missing => write!(f, "missing")?,
+            ImportLocationKind::NoImport => write!(f, "<no imports>")?,
+        }
+        match self.mode {
+            ImportMode::Code => Ok(()),
+            ImportMode::RawText => write!(f, " as Text"),
+            ImportMode::Location => write!(f, " as Location"),
+        }
+    }
+}
+
 impl ImportLocation {
     pub fn dhall_code_of_unknown_origin() -> Self {
         ImportLocation {
@@ -194,20 +247,58 @@ impl ImportLocation {
     }
     pub fn remote_dhall_code(url: Url) -> Self {
         ImportLocation {
-            kind: ImportLocationKind::Remote(url),
+            kind: ImportLocationKind::Remote(url, Vec::new()),
             mode: ImportMode::Code,
         }
     }
 
+    pub(crate) fn kind(&self) -> &ImportLocationKind {
+        &self.kind
+    }
+    /// How the contents of this location are interpreted.
+    pub fn mode(&self) -> ImportMode {
+        self.mode
+    }
+    /// The path of the file, if this is a local file.
+    pub fn local_path(&self) -> Option<&Path> {
+        match &self.kind {
+            ImportLocationKind::Local(path) => Some(path),
+            _ => None,
+        }
+    }
+    /// The url of the file, if this is a remote file.
+    pub fn remote_url(&self) -> Option<&Url> {
+        match &self.kind {
+            ImportLocationKind::Remote(url, _) => Some(url),
+            _ => None,
+        }
+    }
+    /// The name of the variable, if this is an environment variable.
+    pub fn env_var(&self) -> Option<&str> {
+        match &self.kind {
+            ImportLocationKind::Env(name) => Some(name),
+            _ => None,
+        }
+    }
+    /// Whether resolving this location reads its contents, i.e. it is not an `as Location`
+    /// import.
+    pub(crate) fn is_fetched(&self) -> bool {
+        !matches!(self.mode, ImportMode::Location)
+    }
+
     /// Given an import pointing to `target` found in the current location, compute the next
     /// location, or error if not allowed.
     /// `sanity_check` indicates whether to check if that location is allowed to be referenced,
     /// for example to prevent a remote file from reading an environment variable.
-    fn chain(&self, import: &Import) -> Result<ImportLocation, Error> {
+    fn chain<'cx>(
+        &self,
+        cx: Ctxt<'cx>,
+        import: &Import<'cx>,
+    ) -> Result<ImportLocation, Error> {
         // Makes no sense to chain an import if the current file is not a dhall file.
         assert!(matches!(self.mode, ImportMode::Code));
         if matches!(self.kind, ImportLocationKind::NoImport) {
-            Err(ImportError::UnexpectedImport(import.clone()))?;
+            Err(ImportError::UnexpectedImport(import.map_ref(|_| ())))?;
         }
 
         let kind = match &import.location {
@@ -215,19 +306,19 @@ impl ImportLocation {
                 self.kind.chain_local(*prefix, path)?
             }
             ImportTarget::Remote(remote) => {
-                if matches!(self.kind, ImportLocationKind::Remote(..))
-                    && !matches!(import.mode, ImportMode::Location)
-                {
-                    // TODO: allow if CORS check passes
-                    return Err(ImportError::SanityCheck.into());
-                }
+                // Imports from a remote file to a different origin are subject to a CORS
+                // check when the file gets fetched; see `cors_origin`.
                 let mut url = Url::parse(&format!(
                     "{}://{}",
                     remote.scheme, remote.authority
                 ))?;
                 url.set_path(&remote.path.file_path.iter().join("/"));
                 url.set_query(remote.query.as_ref().map(String::as_ref));
-                ImportLocationKind::Remote(url)
+                let headers = match &remote.headers {
+                    Some(headers) => eval_headers(cx, headers)?,
+                    None => Vec::new(),
+                };
+                ImportLocationKind::Remote(url, headers)
             }
             ImportTarget::Env(var_name) => {
                 if matches!(self.kind, ImportLocationKind::Remote(..))
@@ -245,43 +336,91 @@ impl ImportLocation {
         })
     }
 
-    /// Fetches the expression corresponding to this location.
+    /// If importing `target` from the current location requires a CORS check, returns the origin
+    /// that the server of `target` must allow. That's the case for imports from a remote file to
+    /// a remote file with a different origin.
+    fn cors_origin(&self, target: &ImportLocation) -> Option<String> {
+        match (&self.kind, &target.kind) {
+            (
+                ImportLocationKind::Remote(parent, _),
+                ImportLocationKind::Remote(child, _),
+            ) if parent.origin() != child.origin() => {
+                Some(parent.origin().ascii_serialization())
+            }
+            _ => None,
+        }
+    }
+
+    /// Fetches the expression corresponding to this location, along with the imports it
+    /// contains. `cors_origin` is the result of `cors_origin` for the location this was imported
+    /// from.
     fn fetch<'cx>(
         &self,
         env: &mut ImportEnv<'cx>,
+        cors_origin: Option<&str>,
         span: Span,
-    ) -> Result<Typed<'cx>, Error> {
+    ) -> Result<(Typed<'cx>, ResultImports<'cx>), Error> {
         let cx = env.cx();
-        let typed = match self.mode {
+        self.check_offline(env)?;
+        Ok(match self.mode {
             ImportMode::Code => {
-                let parsed = self.kind.fetch_dhall()?;
-                let typed = parsed.resolve_with_env(env)?.typecheck(cx)?;
-                Typed {
-                    // TODO: manage to keep the Nir around. Will need fixing variables.
-                    hir: typed.normalize(cx).to_hir(),
-                    ty: typed.ty,
-                }
+                let parsed = self.kind.fetch_dhall(env, cors_origin)?;
+                let (typed, imports) = resolve_fetched(env, parsed)?;
+                (typed, Some(imports.into()))
             }
             ImportMode::RawText => {
-                let text = self.kind.fetch_text()?;
-                Typed {
+                let text = self.kind.fetch_text(env, cors_origin)?;
+                let typed = Typed {
                     hir: Hir::new(
                         HirKind::Expr(ExprKind::TextLit(text.into())),
                         span,
                     ),
                     ty: Type::from_builtin(cx, Builtin::Text),
-                }
+                };
+                (typed, Some(Box::new([]) as Box<[_]>))
             }
             ImportMode::Location => {
                 let expr = self.kind.to_location();
-                Parsed::from_expr_without_imports(expr)
+                let typed = Parsed::from_expr_without_imports(expr)
                     .resolve(cx)
                     .unwrap()
                     .typecheck(cx)
-                    .unwrap()
+                    .unwrap();
+                (typed, None)
             }
-        };
-        Ok(typed)
+        })
+    }
+
+    /// Like `fetch`, but only resolves the imports of the file, without typechecking it. See
+    /// `ImportEnv::discovering`.
+    fn discover(
+        &self,
+        env: &mut ImportEnv<'_>,
+        cors_origin: Option<&str>,
+    ) -> Result<(), Error> {
+        self.check_offline(env)?;
+        match self.mode {
+            ImportMode::Code => {
+                let parsed = self.kind.fetch_dhall(env, cors_origin)?;
+                resolve_collecting_nodes(env, parsed)?;
+            }
+            ImportMode::RawText => {
+                self.kind.fetch_text(env, cors_origin)?;
+            }
+            ImportMode::Location => {}
+        }
+        Ok(())
+    }
+
+    fn check_offline(&self, env: &ImportEnv<'_>) -> Result<(), Error> {
+        match &self.kind {
+            ImportLocationKind::Remote(url, _)
+                if env.is_offline() && self.is_fetched() =>
+            {
+                Err(ImportError::NetworkDisabled(url.to_string()).into())
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -289,20 +428,6 @@ fn mkexpr(kind: UnspannedExpr) -> Expr {
     Expr::new(kind, Span::Artificial)
 }
 
-// TODO: error handling
-#[cfg(all(not(target_arch = "wasm32"), feature = "reqwest"))]
-pub(crate) fn download_http_text(url: Url) -> Result<String, Error> {
-    Ok(reqwest::blocking::get(url).unwrap().text().unwrap())
-}
-#[cfg(all(not(target_arch = "wasm32"), not(feature = "reqwest")))]
-pub(crate) fn download_http_text(_url: Url) -> Result<String, Error> {
-    panic!("Remote imports are disabled in this build of dhall-rust")
-}
-#[cfg(target_arch = "wasm32")]
-pub(crate) fn download_http_text(_url: Url) -> Result<String, Error> {
-    panic!("Remote imports are not supported on wasm yet")
-}
-
 fn make_aslocation_uniontype() -> Expr {
     let text_type = mkexpr(ExprKind::Builtin(Builtin::Text));
     let mut union = BTreeMap::default();
@@ -313,14 +438,66 @@ fn make_aslocation_uniontype() -> Expr {
     mkexpr(ExprKind::UnionType(union))
 }
 
+/// The type that a `using` headers expression must have.
+fn make_headers_type() -> Expr {
+    let text_type = mkexpr(ExprKind::Builtin(Builtin::Text));
+    let mut record = BTreeMap::default();
+    record.insert("mapKey".into(), text_type.clone());
+    record.insert("mapValue".into(), text_type);
+    mkexpr(ExprKind::Op(OpKind::App(
+        mkexpr(ExprKind::Builtin(Builtin::List)),
+        mkexpr(ExprKind::RecordType(record)),
+    )))
+}
+
+/// Typecheck and normalize the headers of a remote import.
+fn eval_headers<'cx>(
+    cx: Ctxt<'cx>,
+    headers: &Hir<'cx>,
+) -> Result<Headers, Error> {
+    let ty = Parsed::from_expr_without_imports(make_headers_type())
+        .resolve(cx)?
+        .typecheck(cx)?
+        .normalize(cx)
+        .to_hir();
+    let headers = Typed::from_tir(typecheck_with(cx, headers, &ty)?);
+    let headers = headers.normalize(cx);
+    let text_field = |kvs: &HashMap<Label, Nir<'cx>>, field: &str| match kvs
+        .get(&Label::from(field))
+        .map(|v| v.kind())
+    {
+        Some(NirKind::TextLit(t)) => t.as_text(),
+        _ => None,
+    };
+    let elts = match headers.as_nir().kind() {
+        NirKind::EmptyListLit(_) => return Ok(Vec::new()),
+        NirKind::NEListLit(elts) => elts,
+        _ => return Err(ImportError::InvalidHeaders.into()),
+    };
+    elts.iter()
+        .map(|elt| match elt.kind() {
+            NirKind::RecordLit(kvs) => {
+                match (text_field(kvs, "mapKey"), text_field(kvs, "mapValue")) {
+                    (Some(k), Some(v)) => Ok((k, v)),
+                    _ => Err(ImportError::InvalidHeaders.into()),
+                }
+            }
+            _ => Err(ImportError::InvalidHeaders.into()),
+        })
+        .collect()
+}
+
+/// Errors if `import` is an import of code and its value doesn't have the given hash. The hash is
+/// the one of the import, or the one from the lockfile.
 pub fn check_hash<'cx>(
     cx: Ctxt<'cx>,
     import: ImportId<'cx>,
+    hash: &Option<Hash>,
     result: ImportResultId<'cx>,
 ) -> Result<(), Error> {
     let import = &cx[import];
     if let (ImportMode::Code, Some(Hash::SHA256(hash))) =
-        (import.import.mode, &import.import.hash)
+        (import.import.mode, hash)
     {
         let expr = cx[result].hir.to_expr_alpha(cx);
         let actual_hash = expr.sha256_hash()?;
@@ -369,26 +546,146 @@ fn desugar(expr: &Expr) -> Cow<'_, Expr> {
     }
 }
 
-/// Fetch the import and store the result in the global context.
-fn fetch_import<'cx>(
+/// Resolve, typecheck and normalize the contents of a code import, using the semi-semantic cache
+/// if enabled. Also returns the imports it contains.
+///
+/// The key of the semi-semantic cache depends on the values of the imports, so these are always
+/// resolved first; a hit only saves typechecking and normalization.
+fn resolve_fetched<'cx>(
+    env: &mut ImportEnv<'cx>,
+    parsed: Parsed,
+) -> Result<(Typed<'cx>, Vec<ImportNode<'cx>>), Error> {
+    let cx = env.cx();
+    let source = if env.uses_semi_semantic_cache() {
+        Some(binary::encode(&parsed.0)?)
+    } else {
+        None
+    };
+    let (resolved, nodes) = resolve_collecting_nodes(env, parsed)?;
+    let key = match source {
+        Some(source) => Some(semi_semantic_hash(cx, source, &nodes)?),
+        None => None,
+    };
+    if let Some(typed) = key
+        .as_ref()
+        .and_then(|key| env.get_from_semi_semantic_cache(key))
+    {
+        return Ok((typed, nodes));
+    }
+
+    let typed = resolved.typecheck(cx)?;
+    let typed = Typed {
+        // TODO: manage to keep the Nir around. Will need fixing variables.
+        hir: typed.normalize(cx).to_hir(),
+        ty: typed.ty,
+    };
+    if let Some(key) = &key {
+        env.write_to_semi_semantic_cache(key, &typed);
+    }
+    Ok((typed, nodes))
+}
+
+/// The key of an import in the semi-semantic cache: the hash of its binary encoding followed by
+/// the hashes of the values of the imports it depends on.
+fn semi_semantic_hash<'cx>(
+    cx: Ctxt<'cx>,
+    mut data: Vec<u8>,
+    nodes: &[ImportNode<'cx>],
+) -> Result<Hash, Error> {
+    push_dependency_hashes(cx, nodes, &mut data)?;
+    Ok(Hash::SHA256(crate::utils::sha256_hash(&data)))
+}
+
+fn push_dependency_hashes<'cx>(
+    cx: Ctxt<'cx>,
+    nodes: &[ImportNode<'cx>],
+    data: &mut Vec<u8>,
+) -> Result<(), Error> {
+    for &node in nodes {
+        match node {
+            ImportNode::Import(import) => {
+                let typed = cx[import].unwrap_result();
+                data.extend_from_slice(&typed.to_expr_alpha(cx).sha256_hash()?);
+            }
+            ImportNode::Alternative(alt) => {
+                // Only the imports of the selected side were used.
+                let alt = &cx[alt];
+                if alt.unwrap_selected() {
+                    data.push(0);
+                    push_dependency_hashes(cx, &alt.left_imports, data)?;
+                } else {
+                    data.push(1);
+                    push_dependency_hashes(cx, &alt.right_imports, data)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fetch the import and store the result in the global context. Returns `None` if the import
+/// was only discovered; see `ImportEnv::discovering`.
+pub(crate) fn fetch_import<'cx>(
     env: &mut ImportEnv<'cx>,
     import_id: ImportId<'cx>,
-) -> Result<ImportResultId<'cx>, Error> {
+) -> Result<Option<ImportResultId<'cx>>, Error> {
     let cx = env.cx();
     let import = &cx[import_id].import;
     let span = cx[import_id].span.clone();
-    let location = cx[import_id].base_location.chain(import)?;
+    let base_location = &cx[import_id].base_location;
+    let location = base_location.chain(cx, import)?;
+    cx[import_id].set_location(location.clone());
+    let cors_origin = base_location.cors_origin(&location);
+    env.policy().check(&location)?;
+    // Remote imports without a hash can get one from the lockfile.
+    let locked_hash = env.get_locked_hash(&location, &import.hash)?;
+    let hash = import.hash.clone().or(locked_hash);
 
-    // If the hash is in the on-disk cache, return
+    // If the hash is in the on-disk cache or in the bundle, return
     // the cached contents.
-    if let Some(typed) = env.get_from_disk_cache(&import.hash) {
+    let cached = env
+        .get_from_disk_cache(&hash)
+        .or_else(|| env.get_from_bundle(&hash));
+    if let Some(typed) = cached {
+        // The source isn't available, so only the features that the value still uses are
+        // detected. Semi-semantic cache hits don't need this: their source is checked first.
+        if let Some(version) = env.standard_version() {
+            let expr = typed.hir.to_expr_noopts(cx);
+            standard::check_version(&expr, version).map_err(|e| {
+                e.in_import(ImportFrame {
+                    location: location.clone(),
+                    span: span.clone(),
+                })
+            })?;
+        }
         // No need to check the hash, it was checked before reading the file.
         // We also don't write to the in-memory cache, because the location might be completely
         // unrelated to the cached file (e.g. `missing sha256:...` is valid).
         // This actually means that importing many times a same hashed import will take
         // longer than importing many times a same non-hashed import.
         let res_id = cx.push_import_result(typed);
-        return Ok(res_id);
+        return Ok(Some(res_id));
+    }
+
+    // When discovering remote files, we need the value of an import only to check its hash or to
+    // evaluate headers. Otherwise we only look at the imports it contains.
+    if env.is_discovering()
+        && hash.is_none()
+        && !env.is_value_needed(import_id)
+        && env.get_from_mem_cache(&location).is_none()
+    {
+        if !env.is_discovered(&location) {
+            let frame = ImportFrame {
+                location: location.clone(),
+                span,
+            };
+            env.with_cycle_detection(frame.clone(), |env| {
+                location.discover(env, cors_origin.as_deref())
+            })
+            .map_err(|e| e.in_import(frame))?;
+            env.mark_discovered(location);
+        }
+        return Ok(None);
     }
 
     // If the import is in the in-memory cache return the cached contents. Otherwise fetch the
@@ -398,29 +695,33 @@ fn fetch_import<'cx>(
     } else {
         // Resolve this import, making sure that recursive imports don't cycle back to the
         // current one.
-        let res = env.with_cycle_detection(location.clone(), |env| {
-            location.fetch(env, span.clone())
-        });
-        let typed = match res {
-            Ok(typed) => typed,
-            Err(e) => mkerr(
-                ErrorBuilder::new("error")
-                    .span_err(span.clone(), e.to_string())
-                    .format(),
-            )?,
+        let frame = ImportFrame {
+            location: location.clone(),
+            span: span.clone(),
         };
+        let res = env.with_cycle_detection(frame.clone(), |env| {
+            env.without_discovery(|env| {
+                location.fetch(env, cors_origin.as_deref(), span.clone())
+            })
+        });
+        let (typed, imports) = res.map_err(|e| e.in_import(frame))?;
 
-        let res_id = cx.push_import_result(typed);
+        let res_id = cx.push_import_result_with_imports(typed, imports);
         // Cache the mapping from this location to the result.
-        env.write_to_mem_cache(location, res_id);
+        env.write_to_mem_cache(location.clone(), res_id);
         res_id
     };
 
     // Add the resolved import to the on-disk cache if the hash matches.
-    env.check_hash(import_id, res_id)?;
-    env.write_to_disk_cache(&import.hash, res_id);
+    env.check_hash(import_id, &hash, res_id)?;
+    env.write_to_disk_cache(&hash, res_id);
+    // Lock the import if it wasn't already.
+    if hash.is_none() {
+        let hash = env.write_to_lockfile(&location, &import.hash, res_id)?;
+        env.write_to_disk_cache(&hash, res_id);
+    }
 
-    Ok(res_id)
+    Ok(Some(res_id))
 }
 
 /// Part of a tree of imports.
@@ -442,7 +743,7 @@ fn traverse_accumulate<'cx>(
     let cx = env.cx();
     let expr = desugar(expr);
     let kind = match expr.kind() {
-        ExprKind::Var(var) => match name_env.unlabel_var(&var) {
+        ExprKind::Var(var) => match name_env.unlabel_var(var) {
             Some(v) => HirKind::Var(v),
             None => HirKind::MissingVar(var.clone()),
         },
@@ -463,11 +764,36 @@ fn traverse_accumulate<'cx>(
                 base_location,
                 r,
             );
-            let alt =
-                cx.push_import_alternative(imports_l.into(), imports_r.into());
+            let alt = cx.push_import_alternative(
+                imports_l.into(),
+                imports_r.into(),
+                l.span(),
+                r.span(),
+            );
             nodes.push(ImportNode::Alternative(alt));
             HirKind::ImportAlternative(alt, l, r)
         }
+        ExprKind::Import(import) => {
+            // The headers are resolved in an empty context, and their imports are pushed before
+            // this one so that they get resolved first.
+            let first_header_node = nodes.len();
+            let import = import.map_ref(|headers| {
+                traverse_accumulate(
+                    env,
+                    &mut NameEnv::new(),
+                    nodes,
+                    base_location,
+                    headers,
+                )
+            });
+            for &node in &nodes[first_header_node..] {
+                mark_value_needed(env, node);
+            }
+            let import_id =
+                cx.push_import(base_location.clone(), import, expr.span());
+            nodes.push(ImportNode::Import(import_id));
+            HirKind::Import(import_id)
+        }
         kind => {
             let kind = kind.map_ref_maybe_binder(|l, e| {
                 if let Some(l) = l {
@@ -480,43 +806,129 @@ fn traverse_accumulate<'cx>(
                 }
                 hir
             });
-            match kind {
-                ExprKind::Import(import) => {
-                    // TODO: evaluate import headers
-                    let import = import.map_ref(|_| ());
-                    let import_id = cx.push_import(
-                        base_location.clone(),
-                        import,
-                        expr.span(),
-                    );
-                    nodes.push(ImportNode::Import(import_id));
-                    HirKind::Import(import_id)
-                }
-                kind => HirKind::Expr(kind),
-            }
+            HirKind::Expr(kind)
         }
     };
     Hir::new(kind, expr.span())
 }
 
+/// Record that the values of the imports of `node` are needed to resolve other imports.
+fn mark_value_needed<'cx>(env: &mut ImportEnv<'cx>, node: ImportNode<'cx>) {
+    let cx = env.cx();
+    match node {
+        ImportNode::Import(import) => env.mark_value_needed(import),
+        ImportNode::Alternative(alt) => {
+            for &node in
+                cx[alt].left_imports.iter().chain(&*cx[alt].right_imports)
+            {
+                mark_value_needed(env, node);
+            }
+        }
+    }
+}
+
+/// Collect the remote imports that resolving `nodes` will download in any case, i.e. those
+/// that are not on the right of an alternative.
+fn collect_prefetchable<'cx>(
+    env: &ImportEnv<'cx>,
+    nodes: &[ImportNode<'cx>],
+    requests: &mut Vec<(Url, Headers)>,
+) {
+    let cx = env.cx();
+    for &node in nodes {
+        match node {
+            ImportNode::Import(import_id) => {
+                let import = &cx[import_id].import;
+                // Evaluating headers requires resolving earlier imports first.
+                let has_headers = matches!(
+                    &import.location,
+                    ImportTarget::Remote(URL {
+                        headers: Some(_),
+                        ..
+                    })
+                );
+                if has_headers || import.mode == ImportMode::Location {
+                    continue;
+                }
+                let location =
+                    match cx[import_id].base_location.chain(cx, import) {
+                        Ok(location) => location,
+                        Err(_) => continue,
+                    };
+                let locked_hash = env
+                    .get_locked_hash(&location, &import.hash)
+                    .unwrap_or(None);
+                if env.has_cached(&import.hash.clone().or(locked_hash)) {
+                    continue;
+                }
+                if let ImportLocationKind::Remote(url, headers) = &location.kind
+                {
+                    let request = (url.clone(), headers.clone());
+                    if env.policy().check(&location).is_ok()
+                        && env.get_from_mem_cache(&location).is_none()
+                        && !requests.contains(&request)
+                    {
+                        requests.push(request);
+                    }
+                }
+            }
+            ImportNode::Alternative(alt) => {
+                collect_prefetchable(env, &cx[alt].left_imports, requests)
+            }
+        }
+    }
+}
+
 /// Take a list of nodes and recursively resolve them.
 fn resolve_nodes<'cx>(
     env: &mut ImportEnv<'cx>,
     nodes: &[ImportNode<'cx>],
 ) -> Result<(), Error> {
+    // Download the remote imports that don't depend on each other concurrently.
+    if !env.is_offline() {
+        let mut requests = Vec::new();
+        collect_prefetchable(env, nodes, &mut requests);
+        if requests.len() > 1 {
+            env.prefetch_remote(requests);
+        }
+    }
     for &node in nodes {
         match node {
             ImportNode::Import(import) => {
-                let res_id = fetch_import(env, import)?;
-                env.cx()[import].set_resultid(res_id);
+                if let Some(res_id) = fetch_import(env, import)? {
+                    env.cx()[import].set_resultid(res_id);
+                }
             }
             ImportNode::Alternative(alt) => {
                 let alt = &env.cx()[alt];
-                if resolve_nodes(env, &alt.left_imports).is_ok() {
-                    alt.set_selected(true);
-                } else {
-                    resolve_nodes(env, &alt.right_imports)?;
-                    alt.set_selected(false);
+                let left_err = match resolve_nodes(env, &alt.left_imports) {
+                    Ok(()) => {
+                        alt.set_selected(true);
+                        continue;
+                    }
+                    Err(e) => e,
+                };
+                match resolve_nodes(env, &alt.right_imports) {
+                    Ok(()) => alt.set_selected(false),
+                    Err(right_err) => {
+                        // Report why each alternative failed.
+                        let mut failed = Vec::new();
+                        for (span, err) in [
+                            (&alt.left_span, left_err),
+                            (&alt.right_span, right_err),
+                        ] {
+                            match err.into_alternatives_failed() {
+                                Ok(errs) => failed.extend(errs),
+                                Err(error) => failed.push(FailedAlternative {
+                                    span: span.clone(),
+                                    error,
+                                }),
+                            }
+                        }
+                        return Err(
+                            ImportError::AlternativesFailed(failed).into()
+                        );
+                    }
                 }
             }
         }
@@ -528,7 +940,18 @@ fn resolve_with_env<'cx>(
     env: &mut ImportEnv<'cx>,
     parsed: Parsed,
 ) -> Result<Resolved<'cx>, Error> {
+    Ok(resolve_collecting_nodes(env, parsed)?.0)
+}
+
+/// Resolves the imports, also returning the tree of imports that were resolved.
+pub(crate) fn resolve_collecting_nodes<'cx>(
+    env: &mut ImportEnv<'cx>,
+    parsed: Parsed,
+) -> Result<(Resolved<'cx>, Vec<ImportNode<'cx>>), Error> {
     let Parsed(expr, base_location) = parsed;
+    if let Some(version) = env.standard_version() {
+        standard::check_version(&expr, version)?;
+    }
     let mut nodes = Vec::new();
     // First we collect all imports.
     let resolved = traverse_accumulate(
@@ -540,7 +963,12 @@ fn resolve_with_env<'cx>(
     );
     // Then we resolve them and choose sides for the alternatives.
     resolve_nodes(env, &nodes)?;
-    Ok(Resolved(resolved))
+    let resolved = Resolved {
+        hir: resolved,
+        location: base_location,
+        imports: nodes.clone().into(),
+    };
+    Ok((resolved, nodes))
 }
 
 /// Resolves all imports and names. Returns errors if importing failed. Name errors are deferred to
@@ -552,17 +980,28 @@ pub fn resolve<'cx>(
     parsed.resolve_with_env(&mut ImportEnv::new(cx))
 }
 
+/// Resolves all imports and names, fetching imports with the provided fetcher. Returns errors if
+/// importing failed. Name errors are deferred to typechecking.
+pub fn resolve_with<'cx>(
+    cx: Ctxt<'cx>,
+    parsed: Parsed,
+    fetcher: impl ImportFetcher + 'static,
+) -> Result<Resolved<'cx>, Error> {
+    parsed.resolve_with_env(&mut ImportEnv::new(cx).with_fetcher(fetcher))
+}
+
 /// Resolves names, and errors if we find any imports.
 pub fn skip_resolve<'cx>(
     cx: Ctxt<'cx>,
     parsed: Parsed,
 ) -> Result<Resolved<'cx>, Error> {
     let parsed = Parsed::from_expr_without_imports(parsed.0);
-    Ok(resolve(cx, parsed)?)
+    resolve(cx, parsed)
 }
 
 impl Parsed {
-    fn resolve_with_env<'cx>(
+    /// Resolve imports with a custom import environment, e.g. one with an `ImportPolicy`.
+    pub fn resolve_with_env<'cx>(
         self,
         env: &mut ImportEnv<'cx>,
     ) -> Result<Resolved<'cx>, Error> {
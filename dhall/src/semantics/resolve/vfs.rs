@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
+
+use crate::error::{Error, ImportError};
+use crate::semantics::{Headers, ImportFetcher, RemoteFile};
+
+/// A set of files held in memory, that local imports are read from instead of the filesystem.
+///
+/// Relative imports are chained exactly as they would be on disk, so a file can be evaluated
+/// against a tree of files without writing them to a temporary directory. Paths are compared
+/// after lexically removing `.` and `..` components, and relative paths are not made absolute:
+/// `./config/main.dhall` and `config/main.dhall` are the same file, but `/config/main.dhall` is
+/// a different one.
+///
+/// Nothing else is accessed: files that aren't held in memory and environment variables that
+/// weren't added are missing, remote imports fail, and the on-disk cache isn't used. Use
+/// `with_fallback` to fetch these imports from somewhere else, e.g. with `DefaultFetcher`.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), dhall::error::Error> {
+/// use dhall::semantics::VirtualFiles;
+/// use dhall::{Ctxt, Parsed};
+/// use std::path::Path;
+///
+/// let files = VirtualFiles::new()
+///     .with_file("config/main.dhall", "{ port = ./port.dhall }")
+///     .with_file("config/port.dhall", "8080");
+/// let path = Path::new("config/main.dhall");
+/// Ctxt::with_new(|cx| -> Result<(), dhall::error::Error> {
+///     let parsed = Parsed::parse_file_with(path, &files)?;
+///     parsed.resolve_with(cx, files.clone())?.typecheck(cx)?;
+///     Ok(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct VirtualFiles {
+    files: HashMap<PathBuf, String>,
+    env_vars: HashMap<String, String>,
+    fallback: Option<Arc<dyn ImportFetcher + Send + Sync>>,
+}
+
+impl VirtualFiles {
+    pub fn new() -> Self {
+        VirtualFiles::default()
+    }
+
+    /// Add a file, replacing any previous file at the same path.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl ToString) {
+        self.files
+            .insert(normalize(path.as_ref()), contents.to_string());
+    }
+
+    /// Add a file, replacing any previous file at the same path.
+    pub fn with_file(
+        mut self,
+        path: impl AsRef<Path>,
+        contents: impl ToString,
+    ) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Set an environment variable, replacing any previous value.
+    pub fn with_env_var(
+        mut self,
+        name: impl ToString,
+        value: impl ToString,
+    ) -> Self {
+        self.env_vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Fetch the imports that aren't held in memory with `fallback`, instead of failing.
+    pub fn with_fallback(
+        self,
+        fallback: impl ImportFetcher + Send + Sync + 'static,
+    ) -> Self {
+        VirtualFiles {
+            fallback: Some(Arc::new(fallback)),
+            ..self
+        }
+    }
+
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&str> {
+        self.files
+            .get(&normalize(path.as_ref()))
+            .map(String::as_str)
+    }
+
+    /// Iterate over the paths and contents of the files, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.files
+            .iter()
+            .map(|(path, contents)| (path.as_path(), contents.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl fmt::Debug for VirtualFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VirtualFiles")
+            .field("files", &self.files)
+            .field("env_vars", &self.env_vars)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+impl ImportFetcher for VirtualFiles {
+    fn fetch_local(&self, path: &Path) -> Result<String, Error> {
+        match (self.get(path), &self.fallback) {
+            (Some(contents), _) => Ok(contents.to_owned()),
+            (None, Some(fallback)) => fallback.fetch_local(path),
+            (None, None) => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{}: no such virtual file", path.display()),
+            ))?,
+        }
+    }
+    fn fetch_remote(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error> {
+        match &self.fallback {
+            Some(fallback) => fallback.fetch_remote(url, headers),
+            None => {
+                Err(ImportError::RemoteImportsDisabled(url.to_string()).into())
+            }
+        }
+    }
+    fn fetch_env(&self, name: &str) -> Result<String, Error> {
+        match (self.env_vars.get(name), &self.fallback) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(fallback)) => fallback.fetch_env(name),
+            (None, None) => Err(ImportError::MissingEnvVar.into()),
+        }
+    }
+    fn fetch_remote_many(
+        &self,
+        requests: &[(Url, Headers)],
+    ) -> Vec<Result<RemoteFile, Error>> {
+        match &self.fallback {
+            Some(fallback) => fallback.fetch_remote_many(requests),
+            None => requests
+                .iter()
+                .map(|(url, _)| {
+                    Err(ImportError::RemoteImportsDisabled(url.to_string())
+                        .into())
+                })
+                .collect(),
+        }
+    }
+    fn use_default_cache(&self) -> bool {
+        self.fallback
+            .as_ref()
+            .map_or(false, |fallback| fallback.use_default_cache())
+    }
+}
+
+/// Remove `.` components, and `..` components that follow a normal one.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
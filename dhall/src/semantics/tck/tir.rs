@@ -71,7 +71,8 @@ impl<'cx> Type<'cx> {
     pub fn from_builtin(cx: Ctxt<'cx>, b: Builtin) -> Self {
         use Builtin::*;
         match b {
-            Bool | Natural | Integer | Double | Text => {}
+            Bool | Natural | Integer | Double | Text | Date | Time
+            | TimeZone | Bytes => {}
             _ => unreachable!("this builtin is not a type: {}", b),
         }
 
@@ -59,6 +59,10 @@ fn type_one_layer<'cx>(
                 NumKind::Natural(_) => Builtin::Natural,
                 NumKind::Integer(_) => Builtin::Integer,
                 NumKind::Double(_) => Builtin::Double,
+                NumKind::Date(_) => Builtin::Date,
+                NumKind::Time(_) => Builtin::Time,
+                NumKind::TimeZone(_) => Builtin::TimeZone,
+                NumKind::Bytes(_) => Builtin::Bytes,
             },
         ),
         ExprKind::Builtin(b) => {
@@ -147,14 +151,10 @@ fn type_one_layer<'cx>(
             // An empty union type has type Type;
             // an union type with only unary variants also has type Type
             let mut k = Const::Type;
-            for t in kts.values() {
-                if let Some(t) = t {
-                    match t.ty().as_const() {
-                        Some(c) => k = max(k, c),
-                        None => {
-                            return mk_span_err(t.span(), "InvalidVariantType")
-                        }
-                    }
+            for t in kts.values().flatten() {
+                match t.ty().as_const() {
+                    Some(c) => k = max(k, c),
+                    None => return mk_span_err(t.span(), "InvalidVariantType"),
                 }
             }
 
@@ -256,11 +256,11 @@ pub fn type_with<'cx, 'hir>(
         HirKind::Expr(ExprKind::Let(binder, annot, val, body)) => {
             let val_annot = annot
                 .as_ref()
-                .map(|t| Ok(type_with(env, t, None)?.eval_to_type(env)?))
+                .map(|t| type_with(env, t, None)?.eval_to_type(env))
                 .transpose()?;
-            let val = type_with(env, &val, val_annot)?;
+            let val = type_with(env, val, val_annot)?;
             let val_nf = val.eval(env);
-            let body_env = env.insert_value(&binder, val_nf, val.ty().clone());
+            let body_env = env.insert_value(binder, val_nf, val.ty().clone());
             let body = type_with(&body_env, body, None)?;
             let ty = body.ty().clone();
             Tir::from_hir(hir, ty)
@@ -276,7 +276,7 @@ pub fn type_with<'cx, 'hir>(
         if *tir.ty() != annot {
             return mk_span_err(
                 hir.span(),
-                &format!(
+                format!(
                     "annot mismatch: {} != {}",
                     tir.ty().to_expr_tyenv(env),
                     annot.to_expr_tyenv(env)
@@ -0,0 +1,195 @@
+//! Versions of the Dhall standard, and the features that were added in each of them.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::builtins::Builtin;
+use crate::error::{Error, ErrorKind, StandardError};
+use crate::operations::OpKind;
+use crate::syntax::{Expr, ExprKind, NumKind, Span};
+
+/// The version of the Dhall standard whose test suite this crate passes. Some features of later
+/// versions are supported too; see [`Feature`].
+pub const STANDARD_VERSION: StandardVersion = StandardVersion::new(19, 0, 0);
+
+/// A version of the Dhall standard, e.g. `19.0.0`.
+///
+/// # Example
+///
+/// ```
+/// use dhall::standard::{Feature, StandardVersion};
+///
+/// let version: StandardVersion = "20.1.0".parse().unwrap();
+/// assert!(version.has_feature(Feature::TextReplace));
+/// assert!(!version.has_feature(Feature::Bytes));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StandardVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl StandardVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        StandardVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Whether `feature` is part of this version of the standard and is supported by this crate.
+    pub fn has_feature(self, feature: Feature) -> bool {
+        feature.is_supported() && feature.since() <= self
+    }
+}
+
+impl fmt::Display for StandardVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for StandardVersion {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid standard version: {}", s);
+        let s = if s.starts_with('v') { &s[1..] } else { s };
+        let mut parts = s.split('.').map(|part| part.parse::<u32>());
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => {
+                Ok(StandardVersion::new(major, minor, patch))
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+/// A feature that was added to the standard after its first versions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// The `Natural/subtract` builtin
+    NaturalSubtract,
+    /// `with` expressions that update records
+    With,
+    /// The `Text/replace` builtin
+    TextReplace,
+    /// `with` expressions that update `Optional` values, like `x with a.? = 1`
+    WithOptional,
+    /// The `Date`, `Time` and `TimeZone` types and their literals
+    Temporal,
+    /// The `showConstructor` keyword
+    ShowConstructor,
+    /// The `Date/show`, `Time/show` and `TimeZone/show` builtins
+    TemporalShow,
+    /// The `Bytes` type, its literals and `Bytes/show`
+    Bytes,
+}
+
+impl Feature {
+    /// All the features, in the order they were added to the standard.
+    pub const ALL: &'static [Feature] = &[
+        Feature::NaturalSubtract,
+        Feature::With,
+        Feature::TextReplace,
+        Feature::WithOptional,
+        Feature::Temporal,
+        Feature::ShowConstructor,
+        Feature::TemporalShow,
+        Feature::Bytes,
+    ];
+
+    /// The version of the standard that added this feature.
+    pub fn since(self) -> StandardVersion {
+        use Feature::*;
+        match self {
+            NaturalSubtract => StandardVersion::new(13, 0, 0),
+            With => StandardVersion::new(16, 0, 0),
+            TextReplace => StandardVersion::new(19, 0, 0),
+            WithOptional | Temporal => StandardVersion::new(21, 0, 0),
+            ShowConstructor => StandardVersion::new(22, 0, 0),
+            TemporalShow | Bytes => StandardVersion::new(23, 0, 0),
+        }
+    }
+
+    /// Whether this crate implements the feature.
+    pub fn is_supported(self) -> bool {
+        !matches!(self, Feature::WithOptional)
+    }
+
+    /// The feature that this node of an expression belongs to, if any.
+    fn of(expr: &ExprKind<Expr>) -> Option<Feature> {
+        use Builtin::*;
+        Some(match expr {
+            ExprKind::Builtin(NaturalSubtract) => Feature::NaturalSubtract,
+            ExprKind::Op(OpKind::With(..)) => Feature::With,
+            ExprKind::Builtin(TextReplace) => Feature::TextReplace,
+            ExprKind::Builtin(Date)
+            | ExprKind::Builtin(Time)
+            | ExprKind::Builtin(TimeZone)
+            | ExprKind::Num(NumKind::Date(_))
+            | ExprKind::Num(NumKind::Time(_))
+            | ExprKind::Num(NumKind::TimeZone(_)) => Feature::Temporal,
+            ExprKind::Op(OpKind::ShowConstructor(_)) => {
+                Feature::ShowConstructor
+            }
+            ExprKind::Builtin(DateShow)
+            | ExprKind::Builtin(TimeShow)
+            | ExprKind::Builtin(TimeZoneShow) => Feature::TemporalShow,
+            ExprKind::Builtin(Bytes)
+            | ExprKind::Builtin(BytesShow)
+            | ExprKind::Num(NumKind::Bytes(_)) => Feature::Bytes,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Feature::*;
+        f.write_str(match self {
+            NaturalSubtract => "`Natural/subtract`",
+            With => "`with` expressions",
+            TextReplace => "`Text/replace`",
+            WithOptional => "`with` on `Optional` values",
+            Temporal => "`Date`, `Time` and `TimeZone`",
+            ShowConstructor => "`showConstructor`",
+            TemporalShow => "`Date/show`, `Time/show` and `TimeZone/show`",
+            Bytes => "`Bytes`",
+        })
+    }
+}
+
+/// List the features that `expr` uses, with where they are used, in the order they appear.
+/// Imports are not followed.
+pub fn features_used(expr: &Expr) -> Vec<(Feature, Span)> {
+    fn go(expr: &Expr, found: &mut Vec<(Feature, Span)>) {
+        if let Some(feature) = Feature::of(expr.kind()) {
+            found.push((feature, expr.span()));
+        }
+        expr.kind().map_ref(|e| go(e, found));
+    }
+    let mut found = Vec::new();
+    go(expr, &mut found);
+    found
+}
+
+/// Errors if `expr` uses a feature that was added after `version` of the standard.
+pub(crate) fn check_version(
+    expr: &Expr,
+    version: StandardVersion,
+) -> Result<(), Error> {
+    match features_used(expr)
+        .into_iter()
+        .find(|(feature, _)| feature.since() > version)
+    {
+        Some((feature, span)) => {
+            Err(Error::new(ErrorKind::Standard(StandardError {
+                feature,
+                version,
+                span,
+            })))
+        }
+        None => Ok(()),
+    }
+}
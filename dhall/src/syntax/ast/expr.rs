@@ -57,6 +57,42 @@ pub enum NumKind {
     Integer(Integer),
     ///  `3.24`
     Double(Double),
+    ///  `2021-01-31`
+    Date(DateLit),
+    ///  `12:00:00.5`
+    Time(TimeLit),
+    ///  `+01:00`
+    TimeZone(TimeZoneLit),
+    ///  `0x"00ff"`
+    Bytes(Vec<u8>),
+}
+
+/// A `Date` literal. It is always a valid date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateLit {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// A `Time` literal. Leap seconds are not supported.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeLit {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// The digits after the decimal point of the seconds, if any. Trailing zeros are kept
+    /// since the precision is part of the value.
+    pub fraction: String,
+}
+
+/// A `TimeZone` literal, i.e. an offset from UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TimeZoneLit {
+    /// `+00:00` and `-00:00` are different values.
+    pub positive: bool,
+    pub hours: u8,
+    pub minutes: u8,
 }
 
 /// Syntax tree for expressions
@@ -159,6 +195,37 @@ impl<SE> ExprKind<SE> {
     }
 }
 
+impl DateLit {
+    /// Whether this is a date of the proleptic Gregorian calendar.
+    pub fn is_valid(&self) -> bool {
+        let leap = self.year % 4 == 0
+            && (self.year % 100 != 0 || self.year % 400 == 0);
+        let days_in_month = match self.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return false,
+        };
+        self.year <= 9999 && 1 <= self.day && self.day <= days_in_month
+    }
+}
+
+impl TimeLit {
+    pub fn is_valid(&self) -> bool {
+        self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+            && self.fraction.bytes().all(|b| b.is_ascii_digit())
+    }
+}
+
+impl TimeZoneLit {
+    pub fn is_valid(&self) -> bool {
+        self.hours < 24 && self.minutes < 60
+    }
+}
+
 impl Expr {
     pub fn as_ref(&self) -> &UnspannedExpr {
         &self.kind
@@ -1,9 +1,9 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// A location in the source text
 #[derive(Debug, Clone)]
 pub struct ParsedSpan {
-    input: Rc<str>,
+    input: Arc<str>,
     /// # Safety
     ///
     /// Must be a valid character boundary index into `input`.
@@ -43,7 +43,7 @@ impl ParsedSpan {
 }
 
 impl Span {
-    pub fn make(input: Rc<str>, sp: pest::Span) -> Self {
+    pub fn make(input: Arc<str>, sp: pest::Span) -> Self {
         Span::Parsed(ParsedSpan {
             input,
             start: sp.start(),
@@ -51,6 +51,14 @@ impl Span {
         })
     }
 
+    /// The range of bytes of the input covered by this span, if it points to an input location.
+    pub(crate) fn byte_range(&self) -> Option<(usize, usize)> {
+        match self {
+            Span::Parsed(span) => Some((span.start, span.end)),
+            _ => None,
+        }
+    }
+
     /// Takes the union of the two spans, i.e. the range of input covered by the two spans plus any
     /// input between them. Assumes that the spans come from the same input. Fails if one of the
     /// spans does not point to an input location.
@@ -58,7 +66,7 @@ impl Span {
         use std::cmp::{max, min};
         use Span::*;
         match (self, other) {
-            (Parsed(x), Parsed(y)) if Rc::ptr_eq(&x.input, &y.input) => {
+            (Parsed(x), Parsed(y)) if Arc::ptr_eq(&x.input, &y.input) => {
                 Parsed(ParsedSpan {
                     input: x.input.clone(),
                     start: min(x.start, y.start),
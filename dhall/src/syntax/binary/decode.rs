@@ -1,6 +1,7 @@
 use itertools::Itertools;
 use serde::de;
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fmt;
 use std::iter::FromIterator;
 
@@ -8,9 +9,9 @@ use crate::error::DecodeError;
 use crate::operations::OpKind;
 use crate::syntax;
 use crate::syntax::{
-    Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode, ImportTarget,
-    Integer, InterpolatedText, Label, Natural, NumKind, Scheme, Span,
-    UnspannedExpr, URL, V,
+    DateLit, Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode,
+    ImportTarget, Integer, InterpolatedText, Label, Natural, NumKind, Scheme,
+    Span, TimeLit, TimeZoneLit, UnspannedExpr, URL, V,
 };
 type DecodedExpr = Expr;
 
@@ -33,6 +34,7 @@ pub enum Value {
     Array(Vec<Value>),
     Object(BTreeMap<String, Value>),
     Bytes(Vec<u8>),
+    Tag(u64, Box<Value>),
 }
 
 // Should probably rename this
@@ -40,6 +42,26 @@ fn rc(x: UnspannedExpr) -> Expr {
     Expr::new(x, Span::Decoded)
 }
 
+fn small_int<T: TryFrom<u64>>(n: u64, what: &str) -> Result<T, DecodeError> {
+    T::try_from(n).map_err(|_| DecodeError::WrongFormatError(what.to_owned()))
+}
+
+/// Read the magnitude of a CBOR bignum (the contents of tags 2 and 3). Dhall numbers are limited
+/// to 64 bits, so larger ones are rejected.
+fn bignum(n: &Value, what: &str) -> Result<u64, DecodeError> {
+    let err = || DecodeError::WrongFormatError(what.to_owned());
+    let bytes = match n {
+        Value::Bytes(bytes) => bytes,
+        _ => return Err(err()),
+    };
+    let leading_zeros = bytes.iter().take_while(|b| **b == 0).count();
+    let bytes = &bytes[leading_zeros..];
+    if bytes.len() > 8 {
+        return Err(err());
+    }
+    Ok(bytes.iter().fold(0, |n, b| n << 8 | u64::from(*b)))
+}
+
 fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
     use crate::builtins::Builtin;
     use crate::operations::BinOp;
@@ -83,16 +105,16 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                             .to_owned(),
                     ));
                 }
-                let mut f = cbor_value_to_dhall(&f)?;
+                let mut f = cbor_value_to_dhall(f)?;
                 for a in args {
-                    let a = cbor_value_to_dhall(&a)?;
+                    let a = cbor_value_to_dhall(a)?;
                     f = rc(Op(App(f, a)))
                 }
                 return Ok(f);
             }
             [U64(1), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Lam(Label::from("_"), x, y)
             }
             [U64(1), String(l), x, y] => {
@@ -101,14 +123,14 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                         "`_` variable was encoded incorrectly".to_owned(),
                     ));
                 }
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 let l = Label::from(l.as_str());
                 Lam(l, x, y)
             }
             [U64(2), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Pi(Label::from("_"), x, y)
             }
             [U64(2), String(l), x, y] => {
@@ -117,19 +139,19 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                         "`_` variable was encoded incorrectly".to_owned(),
                     ));
                 }
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 let l = Label::from(l.as_str());
                 Pi(l, x, y)
             }
             [U64(3), U64(13), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Op(Completion(x, y))
             }
             [U64(3), U64(n), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 use BinOp::*;
                 let op = match n {
                     0 => BoolOr,
@@ -154,7 +176,7 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 Op(BinOp(op, x, y))
             }
             [U64(4), t] => {
-                let t = cbor_value_to_dhall(&t)?;
+                let t = cbor_value_to_dhall(t)?;
                 EmptyListLit(rc(Op(App(
                     rc(ExprKind::Builtin(Builtin::List)),
                     t,
@@ -168,31 +190,31 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 NEListLit(rest)
             }
             [U64(5), Null, x] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 SomeLit(x)
             }
             // Old-style optional literals
             [U64(5), t] => {
-                let t = cbor_value_to_dhall(&t)?;
+                let t = cbor_value_to_dhall(t)?;
                 Op(App(rc(ExprKind::Builtin(Builtin::OptionalNone)), t))
             }
             [U64(5), t, x] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let t = cbor_value_to_dhall(&t)?;
+                let x = cbor_value_to_dhall(x)?;
+                let t = cbor_value_to_dhall(t)?;
                 Annot(
                     rc(SomeLit(x)),
                     rc(Op(App(rc(ExprKind::Builtin(Builtin::Optional)), t))),
                 )
             }
             [U64(6), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Op(Merge(x, y, None))
             }
             [U64(6), x, y, z] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
-                let z = cbor_value_to_dhall(&z)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
+                let z = cbor_value_to_dhall(z)?;
                 Op(Merge(x, y, Some(z)))
             }
             [U64(7), Object(map)] => {
@@ -204,14 +226,14 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 RecordLit(map)
             }
             [U64(9), x, String(l)] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 let l = Label::from(l.as_str());
                 Op(Field(x, l))
             }
             [U64(10), x, Array(arr)] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 if let [y] = arr.as_slice() {
-                    let y = cbor_value_to_dhall(&y)?;
+                    let y = cbor_value_to_dhall(y)?;
                     Op(ProjectionByExpr(x, y))
                 } else {
                     return Err(DecodeError::WrongFormatError(
@@ -220,7 +242,7 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 }
             }
             [U64(10), x, rest @ ..] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 let labels = rest
                     .iter()
                     .map(|s| match s {
@@ -242,21 +264,104 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 ))
             }
             [U64(14), x, y, z] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
-                let z = cbor_value_to_dhall(&z)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
+                let z = cbor_value_to_dhall(z)?;
                 Op(BoolIf(x, y, z))
             }
             [U64(15), U64(x)] => Num(NumKind::Natural(*x as Natural)),
-            [U64(16), U64(x)] => Num(NumKind::Integer(*x as Integer)),
+            [U64(15), Tag(2, n)] => {
+                Num(NumKind::Natural(bignum(n, "natural")?))
+            }
+            [U64(16), U64(x)] => {
+                Num(NumKind::Integer(small_int(*x, "integer")?))
+            }
             [U64(16), I64(x)] => Num(NumKind::Integer(*x as Integer)),
+            [U64(16), Tag(2, n)] => Num(NumKind::Integer(small_int(
+                bignum(n, "integer")?,
+                "integer",
+            )?)),
+            [U64(16), Tag(3, n)] => {
+                // A negative bignum `n` stands for `-1 - n`.
+                let n: Integer = small_int(bignum(n, "integer")?, "integer")?;
+                Num(NumKind::Integer(-1 - n))
+            }
+            [U64(30), U64(year), U64(month), U64(day)] => {
+                let date = DateLit {
+                    year: small_int(*year, "date")?,
+                    month: small_int(*month, "date")?,
+                    day: small_int(*day, "date")?,
+                };
+                if !date.is_valid() {
+                    return Err(DecodeError::WrongFormatError(
+                        "date".to_owned(),
+                    ));
+                }
+                Num(NumKind::Date(date))
+            }
+            [U64(31), U64(hour), U64(minute), Tag(4, seconds)] => {
+                // The seconds are a decimal fraction.
+                let seconds = match &**seconds {
+                    Array(seconds) => seconds,
+                    _ => {
+                        return Err(DecodeError::WrongFormatError(
+                            "time".to_owned(),
+                        ))
+                    }
+                };
+                let (precision, mantissa) = match seconds.as_slice() {
+                    [U64(0), U64(mantissa)] => (0, *mantissa),
+                    // Bounded to avoid allocating huge strings for malformed input.
+                    [I64(exponent), U64(mantissa)]
+                        if *exponent < 0 && *exponent >= -(1 << 16) =>
+                    {
+                        ((-*exponent) as usize, *mantissa)
+                    }
+                    _ => {
+                        return Err(DecodeError::WrongFormatError(
+                            "time".to_owned(),
+                        ))
+                    }
+                };
+                let digits = format!("{:01$}", mantissa, precision + 1);
+                let (second, fraction) =
+                    digits.split_at(digits.len() - precision);
+                let time = TimeLit {
+                    hour: small_int(*hour, "time")?,
+                    minute: small_int(*minute, "time")?,
+                    second: second.parse().map_err(|_| {
+                        DecodeError::WrongFormatError("time".to_owned())
+                    })?,
+                    fraction: fraction.to_owned(),
+                };
+                if !time.is_valid() {
+                    return Err(DecodeError::WrongFormatError(
+                        "time".to_owned(),
+                    ));
+                }
+                Num(NumKind::Time(time))
+            }
+            [U64(32), Bool(positive), U64(hours), U64(minutes)] => {
+                let tz = TimeZoneLit {
+                    positive: *positive,
+                    hours: small_int(*hours, "time zone")?,
+                    minutes: small_int(*minutes, "time zone")?,
+                };
+                if !tz.is_valid() {
+                    return Err(DecodeError::WrongFormatError(
+                        "time zone".to_owned(),
+                    ));
+                }
+                Num(NumKind::TimeZone(tz))
+            }
+            [U64(33), Bytes(b)] => Num(NumKind::Bytes(b.clone())),
             [U64(18), String(first), rest @ ..] => {
                 TextLit(InterpolatedText::from((
                     first.clone(),
                     rest.iter()
                         .tuples()
                         .map(|(x, y)| {
-                            let x = cbor_value_to_dhall(&x)?;
+                            let x = cbor_value_to_dhall(x)?;
                             let y = match y {
                                 String(s) => s.clone(),
                                 _ => {
@@ -271,7 +376,7 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                 )))
             }
             [U64(19), t] => {
-                let t = cbor_value_to_dhall(&t)?;
+                let t = cbor_value_to_dhall(t)?;
                 Assert(t)
             }
             [U64(24), hash, U64(mode), U64(scheme), rest @ ..] => {
@@ -315,7 +420,7 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                         let headers = match rest.next() {
                             Some(Null) => None,
                             Some(x) => {
-                                let x = cbor_value_to_dhall(&x)?;
+                                let x = cbor_value_to_dhall(x)?;
                                 Some(x)
                             }
                             _ => {
@@ -358,7 +463,7 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                             headers,
                         })
                     }
-                    2 | 3 | 4 | 5 => {
+                    2..=5 => {
                         let prefix = match scheme {
                             2 => FilePrefix::Absolute,
                             3 => FilePrefix::Here,
@@ -417,9 +522,9 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                         let x = Label::from(x.as_str());
                         let t = match t {
                             Null => None,
-                            t => Some(cbor_value_to_dhall(&t)?),
+                            t => Some(cbor_value_to_dhall(t)?),
                         };
-                        let v = cbor_value_to_dhall(&v)?;
+                        let v = cbor_value_to_dhall(v)?;
                         Ok((x, t, v))
                     })
                     .collect::<Result<Vec<_>, _>>()?;
@@ -433,26 +538,30 @@ fn cbor_value_to_dhall(data: &Value) -> Result<DecodedExpr, DecodeError> {
                     .fold(expr, |acc, (x, t, v)| rc(Let(x, t, v, acc))));
             }
             [U64(26), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Annot(x, y)
             }
             [U64(27), x] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 Op(ToMap(x, None))
             }
             [U64(27), x, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 Op(ToMap(x, Some(y)))
             }
+            [U64(34), x] => {
+                let x = cbor_value_to_dhall(x)?;
+                Op(ShowConstructor(x))
+            }
             [U64(28), x] => {
-                let x = cbor_value_to_dhall(&x)?;
+                let x = cbor_value_to_dhall(x)?;
                 EmptyListLit(x)
             }
             [U64(29), x, labels, y] => {
-                let x = cbor_value_to_dhall(&x)?;
-                let y = cbor_value_to_dhall(&y)?;
+                let x = cbor_value_to_dhall(x)?;
+                let y = cbor_value_to_dhall(y)?;
                 let labels = match labels {
                     Array(labels) => labels
                         .iter()
@@ -647,6 +756,23 @@ impl<'de> de::Deserialize<'de> for Value {
             {
                 Ok(Value::F64(v))
             }
+
+            // This is how tagged values are deserialized.
+            #[inline]
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                let tag = serde_cbor::tags::current_cbor_tag();
+                let value = de::Deserialize::deserialize(deserializer)?;
+                Ok(match tag {
+                    Some(tag) => Value::Tag(tag, Box::new(value)),
+                    None => value,
+                })
+            }
         }
 
         deserializer.deserialize_any(ValueVisitor)
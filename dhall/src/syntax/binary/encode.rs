@@ -1,3 +1,4 @@
+use serde_cbor::tags::Tagged;
 use std::collections::BTreeMap;
 use std::vec;
 
@@ -73,6 +74,22 @@ where
             let n: f64 = (*n).into();
             ser.serialize_f64(n)
         }
+        Num(Date(d)) => ser_seq!(ser; tag(30), d.year, d.month, d.day),
+        Num(Time(t)) => {
+            // The seconds are a decimal fraction (CBOR tag 4), with as many digits as were
+            // written.
+            let digits = format!("{}{}", t.second, t.fraction);
+            let mantissa: u64 = digits.parse().map_err(|_| {
+                serde::ser::Error::custom("too many digits in time literal")
+            })?;
+            let exponent = -(t.fraction.len() as i64);
+            let seconds = Tagged::new(Some(4), (exponent, mantissa));
+            ser_seq!(ser; tag(31), t.hour, t.minute, seconds)
+        }
+        Num(TimeZone(tz)) => {
+            ser_seq!(ser; tag(32), tz.positive, tz.hours, tz.minutes)
+        }
+        Num(Bytes(b)) => ser_seq!(ser; tag(33), Serialize::Bytes(b.clone())),
         Op(BoolIf(x, y, z)) => {
             ser_seq!(ser; tag(14), expr(x), expr(y), expr(z))
         }
@@ -163,6 +180,7 @@ where
         }
         Op(ToMap(x, None)) => ser_seq!(ser; tag(27), expr(x)),
         Op(ToMap(x, Some(y))) => ser_seq!(ser; tag(27), expr(x), expr(y)),
+        Op(ShowConstructor(x)) => ser_seq!(ser; tag(34), expr(x)),
         Op(Projection(x, ls)) => ser.collect_seq(
             once(tag(10))
                 .chain(once(expr(x)))
@@ -1,19 +1,24 @@
+// `pest` errors are large, but the parser only returns one when it gives up.
+#![allow(clippy::result_large_err)]
+
 use itertools::Itertools;
 use pest::prec_climber as pcl;
 use pest::prec_climber::PrecClimber;
 use std::collections::{BTreeMap, BTreeSet};
 use std::iter::once;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use pest_consume::{match_nodes, Parser};
 
+use crate::error::OverflowError;
 use crate::operations::OpKind::*;
 use crate::syntax::ExprKind::*;
 use crate::syntax::NumKind::*;
 use crate::syntax::{
-    Double, Expr, FilePath, FilePrefix, Hash, ImportMode, ImportTarget,
-    Integer, InterpolatedText, InterpolatedTextContents, Label, NaiveDouble,
-    Natural, Scheme, Span, UnspannedExpr, URL, V,
+    DateLit, Double, Expr, FilePath, FilePrefix, Hash, ImportMode,
+    ImportTarget, Integer, InterpolatedText, InterpolatedTextContents, Label,
+    NaiveDouble, Natural, Scheme, Span, TimeLit, TimeZoneLit, UnspannedExpr,
+    URL, V,
 };
 
 // This file consumes the parse tree generated by pest and turns it into
@@ -23,7 +28,7 @@ use crate::syntax::{
 
 type ParsedText = InterpolatedText<Expr>;
 type ParsedTextContents = InterpolatedTextContents<Expr>;
-type ParseInput<'input> = pest_consume::Node<'input, Rule, Rc<str>>;
+type ParseInput<'input> = pest_consume::Node<'input, Rule, Arc<str>>;
 
 pub type ParseError = pest::error::Error<Rule>;
 pub type ParseResult<T> = Result<T, ParseError>;
@@ -45,9 +50,16 @@ fn spanned_union(span1: Span, span2: Span, x: UnspannedExpr) -> Expr {
     Expr::new(x, span1.union(&span2))
 }
 
+/// The error for a `Natural` or `Integer` literal `s` that `std` failed to parse. The grammar only
+/// lets digits through, so the literal must be too large.
+fn int_literal_overflow(input: &ParseInput, s: &str) -> ParseError {
+    let err = OverflowError { expr: s.to_owned() };
+    input.error(format!("{}", err))
+}
+
 // Trim the shared indent off of a vec of lines, as defined by the Dhall semantics of multiline
 // literals.
-fn trim_indent(lines: &mut Vec<ParsedText>) {
+fn trim_indent(lines: &mut [ParsedText]) {
     let is_indent = |c: char| c == ' ' || c == '\t';
 
     // There is at least one line so this is safe
@@ -279,23 +291,21 @@ impl DhallParser {
 
                 trim_indent(&mut lines);
 
-                lines
-                    .into_iter()
-                    .intersperse(newline)
+                Itertools::intersperse(lines.into_iter(), newline)
                     .flat_map(InterpolatedText::into_iter)
                     .collect::<ParsedText>()
             }
         ))
     }
-    fn single_quote_char(input: ParseInput) -> ParseResult<&str> {
+    fn single_quote_char(input: ParseInput<'_>) -> ParseResult<&str> {
         Ok(input.as_str())
     }
     #[alias(single_quote_char)]
-    fn escaped_quote_pair(_input: ParseInput) -> ParseResult<&str> {
+    fn escaped_quote_pair(_input: ParseInput<'_>) -> ParseResult<&str> {
         Ok("''")
     }
     #[alias(single_quote_char)]
-    fn escaped_interpolation(_input: ParseInput) -> ParseResult<&str> {
+    fn escaped_interpolation(_input: ParseInput<'_>) -> ParseResult<&str> {
         Ok("${")
     }
 
@@ -350,15 +360,15 @@ impl DhallParser {
 
     #[alias(double_literal)]
     fn NaN(_input: ParseInput) -> ParseResult<Double> {
-        Ok(std::f64::NAN.into())
+        Ok(f64::NAN.into())
     }
     #[alias(double_literal)]
     fn minus_infinity_literal(_input: ParseInput) -> ParseResult<Double> {
-        Ok(std::f64::NEG_INFINITY.into())
+        Ok(f64::NEG_INFINITY.into())
     }
     #[alias(double_literal)]
     fn plus_infinity_literal(_input: ParseInput) -> ParseResult<Double> {
-        Ok(std::f64::INFINITY.into())
+        Ok(f64::INFINITY.into())
     }
 
     #[alias(double_literal)]
@@ -376,26 +386,111 @@ impl DhallParser {
 
     fn natural_literal(input: ParseInput) -> ParseResult<Natural> {
         let s = input.as_str().trim();
-        if s.starts_with("0x") {
+        let res = if s.starts_with("0x") {
             let without_prefix = s.trim_start_matches("0x");
             u64::from_str_radix(without_prefix, 16)
-                .map_err(|e| input.error(format!("{}", e)))
         } else {
-            s.parse().map_err(|e| input.error(format!("{}", e)))
-        }
+            s.parse()
+        };
+        res.map_err(|_| int_literal_overflow(&input, s))
     }
 
     fn integer_literal(input: ParseInput) -> ParseResult<Integer> {
         let s = input.as_str().trim();
         let (sign, rest) = (&s[0..1], &s[1..]);
-        if rest.starts_with("0x") {
+        let res = if rest.starts_with("0x") {
             let without_prefix =
                 sign.to_owned() + rest.trim_start_matches("0x");
             i64::from_str_radix(&without_prefix, 16)
-                .map_err(|e| input.error(format!("{}", e)))
         } else {
-            s.parse().map_err(|e| input.error(format!("{}", e)))
+            s.parse()
+        };
+        res.map_err(|_| int_literal_overflow(&input, s))
+    }
+
+    fn bytes_literal(input: ParseInput) -> ParseResult<Vec<u8>> {
+        let s = input.as_str();
+        // Strip the `0x"` prefix and the closing quote.
+        hex::decode(&s[3..s.len() - 1])
+            .map_err(|e| input.error(format!("{}", e)))
+    }
+
+    fn full_date(input: ParseInput) -> ParseResult<DateLit> {
+        // The grammar ensures the digits are in the right places.
+        let s = input.as_str();
+        let date = DateLit {
+            year: s[0..4].parse().unwrap(),
+            month: s[5..7].parse().unwrap(),
+            day: s[8..10].parse().unwrap(),
+        };
+        if !date.is_valid() {
+            return Err(input.error(format!("Invalid date: '{}'", s)));
+        }
+        Ok(date)
+    }
+
+    fn partial_time(input: ParseInput) -> ParseResult<TimeLit> {
+        let s = input.as_str();
+        let time = TimeLit {
+            hour: s[0..2].parse().unwrap(),
+            minute: s[3..5].parse().unwrap(),
+            second: s[6..8].parse().unwrap(),
+            fraction: s.get(9..).unwrap_or("").to_owned(),
+        };
+        if !time.is_valid() {
+            return Err(input.error(format!("Invalid time: '{}'", s)));
         }
+        Ok(time)
+    }
+
+    fn time_numoffset(input: ParseInput) -> ParseResult<TimeZoneLit> {
+        let s = input.as_str();
+        let tz = TimeZoneLit {
+            positive: s.starts_with('+'),
+            hours: s[1..3].parse().unwrap(),
+            minutes: s[4..6].parse().unwrap(),
+        };
+        if !tz.is_valid() {
+            return Err(input.error(format!("Invalid time zone: '{}'", s)));
+        }
+        Ok(tz)
+    }
+
+    fn time_offset(input: ParseInput) -> ParseResult<TimeZoneLit> {
+        Ok(match_nodes!(input.children();
+            [time_numoffset(tz)] => tz,
+            // `Z`
+            [] => TimeZoneLit {
+                positive: true,
+                hours: 0,
+                minutes: 0,
+            },
+        ))
+    }
+
+    fn temporal_literal(input: ParseInput) -> ParseResult<UnspannedExpr> {
+        let lit = |kind| spanned(input.clone(), Num(kind));
+        let record = |fields: Vec<(&str, Expr)>| {
+            RecordLit(fields.into_iter().map(|(k, v)| (k.into(), v)).collect())
+        };
+        Ok(match_nodes!(input.children();
+            [full_date(d), partial_time(t), time_offset(tz)] => record(vec![
+                ("date", lit(Date(d))),
+                ("time", lit(Time(t))),
+                ("timeZone", lit(TimeZone(tz))),
+            ]),
+            [full_date(d), partial_time(t)] => record(vec![
+                ("date", lit(Date(d))),
+                ("time", lit(Time(t))),
+            ]),
+            [partial_time(t), time_offset(tz)] => record(vec![
+                ("time", lit(Time(t))),
+                ("timeZone", lit(TimeZone(tz))),
+            ]),
+            [full_date(d)] => Num(Date(d)),
+            [partial_time(t)] => Num(Time(t)),
+            [time_numoffset(tz)] => Num(TimeZone(tz)),
+        ))
     }
 
     #[alias(expression, shortcut = true)]
@@ -563,7 +658,7 @@ impl DhallParser {
         ))
     }
     fn posix_environment_variable_character(
-        input: ParseInput,
+        input: ParseInput<'_>,
     ) -> ParseResult<&str> {
         Ok(match input.as_str() {
             "\\\"" => "\"",
@@ -651,6 +746,9 @@ impl DhallParser {
     fn toMap(_input: ParseInput) -> ParseResult<()> {
         Ok(())
     }
+    fn showConstructor(_input: ParseInput) -> ParseResult<()> {
+        Ok(())
+    }
 
     #[alias(expression)]
     fn empty_list_literal(input: ParseInput) -> ParseResult<Expr> {
@@ -806,6 +904,9 @@ impl DhallParser {
             [toMap(()), expression(x)] => {
                 spanned(input, Op(ToMap(x, None)))
             },
+            [showConstructor(()), expression(x)] => {
+                spanned(input, Op(ShowConstructor(x)))
+            },
             [expression(e)] => e,
         ))
     }
@@ -881,6 +982,8 @@ impl DhallParser {
     #[alias(expression, shortcut = true)]
     fn primitive_expression(input: ParseInput) -> ParseResult<Expr> {
         Ok(match_nodes!(input.children();
+            [temporal_literal(e)] => spanned(input, e),
+            [bytes_literal(b)] => spanned(input, Num(Bytes(b))),
             [double_literal(n)] => spanned(input, Num(Double(n))),
             [natural_literal(n)] => spanned(input, Num(Natural(n))),
             [integer_literal(n)] => spanned(input, Num(Integer(n))),
@@ -1034,6 +1137,46 @@ pub fn parse_expr(input_str: &str) -> ParseResult<Expr> {
     ))
 }
 
+/// Parse the whole of `input_str` with the given rule.
+fn parse_whole<T>(
+    input_str: &str,
+    rule: Rule,
+    parse: fn(ParseInput) -> ParseResult<T>,
+) -> ParseResult<T> {
+    let rc_input_str = input_str.to_string().into();
+    let input =
+        DhallParser::parse_with_userdata(rule, input_str, rc_input_str)?
+            .single()?;
+    if input.as_str().len() != input_str.len() {
+        return Err(input.error(format!("Unexpected input: '{}'", input_str)));
+    }
+    parse(input)
+}
+
+impl std::str::FromStr for DateLit {
+    type Err = ParseError;
+    /// Parses a date in the same format as in Dhall, e.g. `2021-01-31`.
+    fn from_str(s: &str) -> ParseResult<Self> {
+        parse_whole(s, Rule::full_date, DhallParser::full_date)
+    }
+}
+
+impl std::str::FromStr for TimeLit {
+    type Err = ParseError;
+    /// Parses a time in the same format as in Dhall, e.g. `12:00:00.5`.
+    fn from_str(s: &str) -> ParseResult<Self> {
+        parse_whole(s, Rule::partial_time, DhallParser::partial_time)
+    }
+}
+
+impl std::str::FromStr for TimeZoneLit {
+    type Err = ParseError;
+    /// Parses a time zone in the same format as in Dhall, e.g. `+01:00`.
+    fn from_str(s: &str) -> ParseResult<Self> {
+        parse_whole(s, Rule::time_numoffset, DhallParser::time_numoffset)
+    }
+}
+
 #[test]
 #[cfg_attr(windows, ignore)]
 // Check that the local copy of the grammar file is in sync with the one from dhall-lang.
@@ -60,6 +60,9 @@ impl UnspannedExpr {
                 a.phase(PrintPhase::Import),
                 b.map(|x| x.phase(PrintPhase::App)),
             )),
+            Op(ShowConstructor(a)) => {
+                Op(ShowConstructor(a.phase(PrintPhase::Import)))
+            }
             Annot(a, b) => Annot(a.phase(Operator), b),
             Op(OpKind::BinOp(op, a, b)) => Op(OpKind::BinOp(
                 op,
@@ -107,7 +110,7 @@ impl UnspannedExpr {
             // Precedence is magically handled by the ordering of BinOps. This is reverse Pratt
             // parsing.
             Op(BinOp(op, _, _)) => phase > PrintPhase::BinOp(*op),
-            Op(App(_, _)) => phase > PrintPhase::App,
+            Op(App(_, _)) | Op(ShowConstructor(_)) => phase > PrintPhase::App,
             Op(Completion(_, _)) => phase > PrintPhase::Import,
             _ => false,
         };
@@ -263,6 +266,9 @@ impl<SE: Display + Clone> Display for OpKind<SE> {
                     write!(f, " : {}", b)?;
                 }
             }
+            ShowConstructor(a) => {
+                write!(f, "showConstructor {}", a)?;
+            }
             Field(a, b) => {
                 write!(f, "{}.", a)?;
                 fmt_label(b, f)?;
@@ -305,11 +311,38 @@ impl Display for NumKind {
             }
             Integer(a) => a.fmt(f)?,
             Double(a) => a.fmt(f)?,
+            Date(a) => a.fmt(f)?,
+            Time(a) => a.fmt(f)?,
+            TimeZone(a) => a.fmt(f)?,
+            Bytes(a) => write!(f, "0x\"{}\"", hex::encode_upper(a))?,
+        }
+        Ok(())
+    }
+}
+
+impl Display for DateLit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Display for TimeLit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        if !self.fraction.is_empty() {
+            write!(f, ".{}", self.fraction)?;
         }
         Ok(())
     }
 }
 
+impl Display for TimeZoneLit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let sign = if self.positive { '+' } else { '-' };
+        write!(f, "{}{:02}:{:02}", sign, self.hours, self.minutes)
+    }
+}
+
 impl<'a> Display for PhasedExpr<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         self.0.as_ref().fmt_phase(f, self.1)
@@ -389,9 +422,9 @@ impl Display for BinOp {
 impl Display for NaiveDouble {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         let v = f64::from(*self);
-        if v == std::f64::INFINITY {
+        if v == f64::INFINITY {
             f.write_str("Infinity")
-        } else if v == std::f64::NEG_INFINITY {
+        } else if v == f64::NEG_INFINITY {
             f.write_str("-Infinity")
         } else if v.is_nan() {
             f.write_str("NaN")
@@ -444,11 +477,8 @@ impl<SubExpr: Display> Display for Import<SubExpr> {
                     Absolute => "",
                 };
                 write!(f, "{}/", prefix)?;
-                let path: String = path
-                    .file_path
-                    .iter()
-                    .map(|c| quote_if_needed(&*c))
-                    .join("/");
+                let path: String =
+                    path.file_path.iter().map(|c| quote_if_needed(c)).join("/");
                 f.write_str(&path)?;
             }
             Remote(url) => {
@@ -458,8 +488,9 @@ impl<SubExpr: Display> Display for Import<SubExpr> {
                 if let Some(q) = &url.query {
                     write!(f, "?{}", q)?
                 }
+                // The parentheses keep a hash after the import from applying to the headers.
                 if let Some(h) = &url.headers {
-                    write!(f, " using {}", h)?
+                    write!(f, " using ({})", h)?
                 }
             }
             Env(s) => {
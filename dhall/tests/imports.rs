@@ -0,0 +1,1841 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+#[cfg(feature = "reqwest")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "reqwest")]
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+#[cfg(feature = "reqwest")]
+use std::sync::{Arc, Condvar, Mutex};
+#[cfg(feature = "reqwest")]
+use std::time::Duration;
+use url::Url;
+
+use dhall::error::{Error, ErrorKind, ImportError};
+use dhall::semantics::{Headers, ImportFetcher, RemoteFile};
+use dhall::syntax::Expr;
+use dhall::*;
+
+/// Serves imports from memory.
+#[derive(Default)]
+struct MapFetcher {
+    files: HashMap<PathBuf, String>,
+    urls: HashMap<String, String>,
+    env: HashMap<String, String>,
+    /// If set, remote imports require this `Authorization` header.
+    token: Option<String>,
+    /// The urls passed to each call of `fetch_remote_many`.
+    batches: RefCell<Vec<Vec<String>>>,
+}
+
+impl ImportFetcher for MapFetcher {
+    fn fetch_local(&self, path: &Path) -> Result<String, Error> {
+        match self.files.get(path) {
+            Some(s) => Ok(s.clone()),
+            None => Err(std::io::Error::from(std::io::ErrorKind::NotFound))?,
+        }
+    }
+    fn fetch_remote(
+        &self,
+        url: &Url,
+        headers: &[(String, String)],
+    ) -> Result<RemoteFile, Error> {
+        if let Some(token) = &self.token {
+            let auth = ("Authorization".to_string(), token.clone());
+            if !headers.contains(&auth) {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))?
+            }
+        }
+        match self.urls.get(url.as_str()) {
+            Some(s) => Ok(s.clone().into()),
+            None => Err(std::io::Error::from(std::io::ErrorKind::NotFound))?,
+        }
+    }
+    fn fetch_env(&self, name: &str) -> Result<String, Error> {
+        match self.env.get(name) {
+            Some(s) => Ok(s.clone()),
+            None => Err(ImportError::MissingEnvVar)?,
+        }
+    }
+    fn fetch_remote_many(
+        &self,
+        requests: &[(Url, Headers)],
+    ) -> Vec<Result<RemoteFile, Error>> {
+        let urls = requests.iter().map(|(url, _)| url.to_string()).collect();
+        self.batches.borrow_mut().push(urls);
+        requests
+            .iter()
+            .map(|(url, headers)| self.fetch_remote(url, headers))
+            .collect()
+    }
+}
+
+#[cfg(feature = "reqwest")]
+/// A canned HTTP response.
+#[derive(Clone)]
+struct Response {
+    status: u16,
+    body: String,
+    allow_origin: Option<String>,
+    /// Announce a longer body than what is sent.
+    truncated: bool,
+}
+
+#[cfg(feature = "reqwest")]
+impl Response {
+    fn new(body: impl ToString) -> Self {
+        Response {
+            status: 200,
+            body: body.to_string(),
+            allow_origin: None,
+            truncated: false,
+        }
+    }
+    fn status(self, status: u16) -> Self {
+        Response { status, ..self }
+    }
+    fn allow_origin(self, origin: impl ToString) -> Self {
+        Response {
+            allow_origin: Some(origin.to_string()),
+            ..self
+        }
+    }
+    fn truncated(self) -> Self {
+        Response {
+            truncated: true,
+            ..self
+        }
+    }
+
+    fn to_http(&self) -> String {
+        let mut head = format!(
+            "HTTP/1.1 {} Whatever\r\nContent-Length: {}\r\n\
+             Connection: close\r\n",
+            self.status,
+            self.body.len() + if self.truncated { 10 } else { 0 },
+        );
+        if let Some(origin) = &self.allow_origin {
+            head += &format!("Access-Control-Allow-Origin: {}\r\n", origin);
+        }
+        format!("{}\r\n{}", head, self.body)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+/// A minimal HTTP server on localhost that serves canned responses.
+struct TestServer {
+    listener: TcpListener,
+}
+
+#[cfg(feature = "reqwest")]
+impl TestServer {
+    fn new() -> Self {
+        TestServer {
+            listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+        }
+    }
+
+    fn origin(&self) -> String {
+        format!("http://{}", self.listener.local_addr().unwrap())
+    }
+
+    /// Serve the given responses, keyed by path, from a background thread. Unknown paths get a
+    /// 404.
+    fn serve(self, routes: HashMap<&'static str, Response>) {
+        std::thread::spawn(move || {
+            let not_found = Response::new("").status(404);
+            for stream in self.listener.incoming() {
+                let mut stream = stream.unwrap();
+                let path = read_request_path(&stream);
+                let response = routes.get(path.as_str()).unwrap_or(&not_found);
+                let _ = stream.write_all(response.to_http().as_bytes());
+            }
+        });
+    }
+
+    /// Like `serve`, but only answers once `concurrent` requests are waiting for an answer. If
+    /// that doesn't happen within a few seconds, requests get a 503.
+    fn serve_concurrently(
+        self,
+        routes: HashMap<&'static str, Response>,
+        concurrent: usize,
+    ) {
+        let routes = Arc::new(routes);
+        let waiting = Arc::new((Mutex::new(0), Condvar::new()));
+        std::thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let mut stream = stream.unwrap();
+                let routes = routes.clone();
+                let waiting = waiting.clone();
+                std::thread::spawn(move || {
+                    let path = read_request_path(&stream);
+                    let (count, all_arrived) = &*waiting;
+                    let mut count = count.lock().unwrap();
+                    *count += 1;
+                    all_arrived.notify_all();
+                    let (_count, wait) = all_arrived
+                        .wait_timeout_while(
+                            count,
+                            Duration::from_secs(5),
+                            |c| *c < concurrent,
+                        )
+                        .unwrap();
+                    let response = match routes.get(path.as_str()) {
+                        _ if wait.timed_out() => Response::new("").status(503),
+                        Some(response) => response.clone(),
+                        None => Response::new("").status(404),
+                    };
+                    let _ = stream.write_all(response.to_http().as_bytes());
+                });
+            }
+        });
+    }
+}
+
+#[cfg(feature = "reqwest")]
+/// Read an HTTP request and return the requested path.
+fn read_request_path(stream: &TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    // Skip the request headers.
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 2 {
+        line.clear();
+    }
+    request_line.split_whitespace().nth(1).unwrap().to_owned()
+}
+
+fn eval_with(fetcher: &Rc<MapFetcher>, s: &str) -> Result<Expr, Error> {
+    Ctxt::with_new(|cx| {
+        Ok(Parsed::parse_str(s)?
+            .resolve_with(cx, fetcher.clone())?
+            .typecheck(cx)?
+            .normalize(cx)
+            .to_expr(cx))
+    })
+}
+
+#[cfg(feature = "reqwest")]
+fn eval_default(s: &str) -> Result<Expr, Error> {
+    Ctxt::with_new(|cx| {
+        Ok(Parsed::parse_str(s)?
+            .resolve(cx)?
+            .typecheck(cx)?
+            .normalize(cx)
+            .to_expr(cx))
+    })
+}
+
+fn eval(s: &str) -> Expr {
+    Parsed::parse_str(s).unwrap().to_expr()
+}
+
+/// The error that made the innermost import fail.
+fn import_error(err: &Error) -> &ImportError {
+    match err.kind() {
+        ErrorKind::Resolve(ImportError::InImport(_, inner)) => {
+            import_error(inner)
+        }
+        ErrorKind::Resolve(err) => err,
+        kind => panic!("not an import error: {:?}", kind),
+    }
+}
+
+#[test]
+fn custom_fetcher() {
+    let mut fetcher = MapFetcher::default();
+    fetcher
+        .files
+        .insert("/virtual/a.dhall".into(), "./b.dhall + 1".into());
+    fetcher.files.insert("/virtual/b.dhall".into(), "41".into());
+    fetcher
+        .urls
+        .insert("https://example.com/c.dhall".into(), "\"remote\"".into());
+    fetcher.env.insert("FOO".into(), "True".into());
+    let fetcher = Rc::new(fetcher);
+
+    assert_eq!(eval_with(&fetcher, "/virtual/a.dhall").unwrap(), eval("42"));
+    assert_eq!(
+        eval_with(&fetcher, "/virtual/b.dhall as Text").unwrap(),
+        eval("\"41\"")
+    );
+    assert_eq!(
+        eval_with(&fetcher, "https://example.com/c.dhall").unwrap(),
+        eval("\"remote\"")
+    );
+    assert_eq!(eval_with(&fetcher, "env:FOO").unwrap(), eval("True"));
+    assert_eq!(
+        eval_with(&fetcher, "env:BAR ? /virtual/b.dhall").unwrap(),
+        eval("41")
+    );
+    assert!(eval_with(&fetcher, "/virtual/missing.dhall").is_err());
+}
+
+#[test]
+fn remote_import_headers() {
+    let mut fetcher = MapFetcher {
+        token: Some("Bearer secret".into()),
+        ..MapFetcher::default()
+    };
+    fetcher
+        .urls
+        .insert("https://example.com/a.dhall".into(), "./b.dhall".into());
+    fetcher
+        .urls
+        .insert("https://example.com/b.dhall".into(), "42".into());
+    fetcher.files.insert(
+        "/virtual/headers.dhall".into(),
+        r#"[ { mapKey = "Authorization", mapValue = "Bearer secret" } ]"#
+            .into(),
+    );
+    fetcher
+        .env
+        .insert("TOKEN".into(), r#""Bearer secret""#.into());
+    let fetcher = Rc::new(fetcher);
+
+    assert!(eval_with(&fetcher, "https://example.com/a.dhall").is_err());
+    // Headers are passed on to relative imports.
+    assert_eq!(
+        eval_with(
+            &fetcher,
+            r#"https://example.com/a.dhall using
+                 [ { mapKey = "Authorization", mapValue = "Bearer secret" } ]"#
+        )
+        .unwrap(),
+        eval("42")
+    );
+    // The headers expression can itself contain imports.
+    assert_eq!(
+        eval_with(
+            &fetcher,
+            "https://example.com/b.dhall using /virtual/headers.dhall"
+        )
+        .unwrap(),
+        eval("42")
+    );
+    assert_eq!(
+        eval_with(
+            &fetcher,
+            r#"https://example.com/b.dhall using
+                 (toMap { Authorization = env:TOKEN })"#
+        )
+        .unwrap(),
+        eval("42")
+    );
+    // Headers must have the right type.
+    assert!(eval_with(
+        &fetcher,
+        r#"https://example.com/b.dhall using [ { header = "a", value = "b" } ]"#
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(feature = "reqwest")]
+fn remote_import_cors() {
+    let server_a = TestServer::new();
+    let server_b = TestServer::new();
+    let a = server_a.origin();
+    let b = server_b.origin();
+
+    let mut routes_a = HashMap::new();
+    routes_a.insert("/same.dhall", Response::new("./one.dhall"));
+    routes_a.insert("/one.dhall", Response::new("1"));
+    routes_a.insert("/to-open.dhall", Response::new(format!("{}/open", b)));
+    routes_a
+        .insert("/to-allowed.dhall", Response::new(format!("{}/allowed", b)));
+    routes_a.insert("/to-denied.dhall", Response::new(format!("{}/denied", b)));
+    routes_a
+        .insert("/to-private.dhall", Response::new(format!("{}/private", b)));
+    routes_a.insert(
+        "/to-private-text.dhall",
+        Response::new(format!("{}/private as Text", b)),
+    );
+    let mut routes_b = HashMap::new();
+    routes_b.insert("/open", Response::new("2").allow_origin("*"));
+    routes_b.insert("/allowed", Response::new("3").allow_origin(&a));
+    routes_b.insert(
+        "/denied",
+        Response::new("4").allow_origin("http://example.com"),
+    );
+    routes_b.insert("/private", Response::new("5"));
+    server_a.serve(routes_a);
+    server_b.serve(routes_b);
+
+    let import = |path: &str| eval_default(&format!("{}{}", a, path));
+    // Same origin needs no check.
+    assert_eq!(import("/same.dhall").unwrap(), eval("1"));
+    assert_eq!(import("/to-open.dhall").unwrap(), eval("2"));
+    assert_eq!(import("/to-allowed.dhall").unwrap(), eval("3"));
+    assert!(import("/to-denied.dhall")
+        .unwrap_err()
+        .to_string()
+        .contains("CorsCheck"));
+    assert!(import("/to-private.dhall")
+        .unwrap_err()
+        .to_string()
+        .contains("CorsCheck"));
+    assert!(import("/to-private-text.dhall")
+        .unwrap_err()
+        .to_string()
+        .contains("CorsCheck"));
+    // Only imports from a remote file are checked.
+    assert_eq!(eval_default(&format!("{}/private", b)).unwrap(), eval("5"));
+}
+
+#[test]
+#[cfg(feature = "reqwest")]
+fn remote_import_errors() {
+    let server = TestServer::new();
+    let origin = server.origin();
+    let mut routes = HashMap::new();
+    routes.insert("/ok.dhall", Response::new("1"));
+    routes.insert("/error.dhall", Response::new("2").status(500));
+    routes.insert("/truncated.dhall", Response::new("3").truncated());
+    server.serve(routes);
+    // Nothing listens on this port anymore.
+    let closed = TestServer::new().origin();
+
+    let import = |path: &str| eval_default(&format!("{}{}", origin, path));
+    assert_eq!(import("/ok.dhall").unwrap(), eval("1"));
+    let err = import("/missing.dhall").unwrap_err();
+    match import_error(&err) {
+        ImportError::HttpStatus { url, status: 404 } => {
+            assert_eq!(url, &format!("{}/missing.dhall", origin))
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+    assert!(err.to_string().contains(&format!(
+        "failed to download {}/missing.dhall: HTTP status 404",
+        origin
+    )));
+    let err = import("/error.dhall").unwrap_err();
+    assert!(matches!(
+        import_error(&err),
+        ImportError::HttpStatus { status: 500, .. }
+    ));
+    let err = import("/truncated.dhall").unwrap_err();
+    assert!(matches!(
+        import_error(&err),
+        ImportError::ResponseDecode { .. }
+    ));
+    let err = eval_default(&format!("{}/ok.dhall", closed)).unwrap_err();
+    assert!(matches!(import_error(&err), ImportError::Network { .. }));
+    // Failed downloads fall back to the alternative.
+    assert_eq!(import("/missing.dhall ? 42").unwrap(), eval("42"));
+    assert_eq!(
+        eval_default(&format!("{}/ok.dhall ? 42", closed)).unwrap(),
+        eval("42")
+    );
+}
+
+#[test]
+fn import_policy() {
+    use dhall::semantics::{ImportEnv, ImportPolicy};
+
+    let mut fetcher = MapFetcher::default();
+    fetcher.files.insert("/root/a.dhall".into(), "1".into());
+    fetcher
+        .files
+        .insert("/root/sub/b.dhall".into(), "../a.dhall + 1".into());
+    fetcher
+        .files
+        .insert("/root/escape.dhall".into(), "../etc/passwd as Text".into());
+    fetcher.files.insert("/etc/passwd".into(), "root".into());
+    fetcher
+        .urls
+        .insert("https://example.com/c.dhall".into(), "3".into());
+    fetcher
+        .urls
+        .insert("https://example.org/d.dhall".into(), "4".into());
+    fetcher.env.insert("HOME".into(), "5".into());
+    fetcher.env.insert("SECRET".into(), "6".into());
+    let fetcher = Rc::new(fetcher);
+
+    let eval_policy = |policy: &ImportPolicy, s: &str| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(fetcher.clone())
+                .with_policy(policy.clone());
+            Ok::<_, Error>(
+                Parsed::parse_str(s)?
+                    .resolve_with_env(&mut env)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+    };
+    let allowed = |policy: &ImportPolicy, s: &str| {
+        eval_policy(policy, s).map_err(|e| e.to_string())
+    };
+    let forbidden = |policy: &ImportPolicy, s: &str| {
+        eval_policy(policy, s)
+            .unwrap_err()
+            .to_string()
+            .contains("the import policy forbids")
+    };
+
+    let policy = ImportPolicy::deny_all();
+    assert!(forbidden(&policy, "/root/a.dhall"));
+    assert!(forbidden(&policy, "https://example.com/c.dhall"));
+    assert!(forbidden(&policy, "env:HOME"));
+    // Nothing is read for `as Location` imports.
+    assert!(allowed(&policy, "env:HOME as Location").is_ok());
+    // Forbidden imports fall back to the alternative.
+    assert_eq!(allowed(&policy, "env:HOME ? 0"), Ok(eval("0")));
+
+    let policy = ImportPolicy::default()
+        .local_root("/root")
+        .allowed_hosts(vec!["example.com"])
+        .allowed_env_vars(vec!["HOME"]);
+    assert_eq!(allowed(&policy, "/root/sub/b.dhall"), Ok(eval("2")));
+    assert!(forbidden(&policy, "/root/escape.dhall"));
+    assert!(forbidden(&policy, "/root/../etc/passwd"));
+    assert!(forbidden(&policy, "~/a.dhall"));
+    assert_eq!(
+        allowed(&policy, "https://example.com/c.dhall"),
+        Ok(eval("3"))
+    );
+    assert!(forbidden(&policy, "https://example.org/d.dhall"));
+    assert_eq!(allowed(&policy, "env:HOME"), Ok(eval("5")));
+    assert!(forbidden(&policy, "env:SECRET"));
+}
+
+#[test]
+#[cfg(unix)]
+fn import_policy_symlinks() {
+    use dhall::semantics::{ImportEnv, ImportPolicy};
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-policy-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let root = dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::create_dir_all(dir.join("secret")).unwrap();
+    std::fs::write(dir.join("secret/passwd"), "root").unwrap();
+    std::fs::write(root.join("a.dhall"), "1").unwrap();
+    std::os::unix::fs::symlink(dir.join("secret"), root.join("link")).unwrap();
+    std::os::unix::fs::symlink(&root, dir.join("root-link")).unwrap();
+
+    let eval_policy = |policy: &ImportPolicy, path: &Path| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx).with_policy(policy.clone());
+            let s = format!("{} as Text", path.display());
+            Ok::<_, Error>(
+                Parsed::parse_str(&s)?
+                    .resolve_with_env(&mut env)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    let policy = ImportPolicy::default().local_root(&root);
+    assert_eq!(
+        eval_policy(&policy, &root.join("a.dhall")),
+        Ok(eval(r#""1""#))
+    );
+    // The link is inside the root but points outside of it.
+    assert!(eval_policy(&policy, &root.join("link/passwd"))
+        .unwrap_err()
+        .contains("the import policy forbids"));
+    // A root given through a symlink still allows the files it contains.
+    let policy = ImportPolicy::default().local_root(dir.join("root-link"));
+    assert_eq!(
+        eval_policy(&policy, &root.join("a.dhall")),
+        Ok(eval(r#""1""#))
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn offline_mode() {
+    use dhall::semantics::{Bundle, ImportEnv};
+    use dhall::syntax::binary;
+
+    let mut fetcher = MapFetcher::default();
+    fetcher.files.insert("/a.dhall".into(), "1".into());
+    fetcher
+        .urls
+        .insert("https://example.com/b.dhall".into(), "2".into());
+    fetcher
+        .urls
+        .insert("https://example.com/c.dhall".into(), "3".into());
+    let fetcher = Rc::new(fetcher);
+
+    let mut bundle = Bundle::new();
+    let hash = bundle.insert(binary::encode(&eval("3")).unwrap().into());
+    let bundle = Bundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+    assert!(bundle.contains(&hash));
+
+    let eval_offline = |s: &str| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(fetcher.clone())
+                .with_bundle(bundle.clone())
+                .offline(true);
+            Ok::<_, Error>(
+                Parsed::parse_str(s)?
+                    .resolve_with_env(&mut env)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    // Local imports are still read.
+    assert_eq!(eval_offline("/a.dhall"), Ok(eval("1")));
+    // Hashed remote imports are served from the bundle.
+    assert_eq!(
+        eval_offline(&format!("https://example.com/c.dhall {}", hash)),
+        Ok(eval("3"))
+    );
+    // Other remote imports are never downloaded.
+    let err = eval_offline("https://example.com/b.dhall").unwrap_err();
+    assert!(err.contains(
+        "network access is disabled (offline mode): cannot fetch \
+         https://example.com/b.dhall"
+    ));
+    assert!(err.contains("https://example.com/b.dhall"));
+    assert!(eval_offline("https://example.com/b.dhall as Location").is_ok());
+    assert_eq!(
+        eval_offline("https://example.com/b.dhall ? 0"),
+        Ok(eval("0"))
+    );
+}
+
+#[test]
+fn cache_config() {
+    use dhall::semantics::{Cache, ImportEnv};
+    use dhall::syntax::Hash;
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-cache-config-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut fetcher = MapFetcher::default();
+    fetcher
+        .urls
+        .insert("https://example.com/c.dhall".into(), "3".into());
+    let fetcher = Rc::new(fetcher);
+    let hash = Hash::SHA256(eval("3").sha256_hash().unwrap());
+    let import = format!("https://example.com/c.dhall {}", hash);
+
+    let eval_env = |make_env: &dyn Fn(ImportEnv) -> ImportEnv| {
+        Ctxt::with_new(|cx| {
+            let mut env = make_env(ImportEnv::new(cx));
+            Ok::<_, Error>(
+                Parsed::parse_str(&import)?
+                    .resolve_with_env(&mut env)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    // A read-only cache isn't written to.
+    let res = eval_env(&|env| {
+        env.with_fetcher(fetcher.clone())
+            .with_cache(Cache::at(&dir).read_only(true))
+    });
+    assert_eq!(res, Ok(eval("3")));
+    assert!(!dir.exists());
+
+    let res = eval_env(&|env| {
+        env.with_fetcher(fetcher.clone())
+            .with_cache(Cache::at(&dir))
+    });
+    assert_eq!(res, Ok(eval("3")));
+    assert!(dir.read_dir().unwrap().count() == 1);
+
+    // The entry is now found in the cache.
+    let res = eval_env(&|env| {
+        env.with_fetcher(MapFetcher::default())
+            .with_cache(Cache::at(&dir).read_only(true))
+            .offline(true)
+    });
+    assert_eq!(res, Ok(eval("3")));
+    let res = eval_env(&|env| {
+        env.with_fetcher(MapFetcher::default())
+            .without_cache()
+            .offline(true)
+    });
+    assert!(res.unwrap_err().contains("network access is disabled"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Writes large entries to the cache in `dir` and checks that they can be read back. Each entry
+/// is missing at first, so when several threads or processes run this at once, some of them read
+/// an entry while others write it. Threads that share `barrier` start each entry together.
+fn hammer_cache(
+    dir: &Path,
+    iterations: usize,
+    barrier: Option<&std::sync::Barrier>,
+) {
+    use dhall::semantics::Cache;
+    use dhall::syntax::{ExprKind, Hash, NumKind, Span};
+
+    let natural =
+        |n| Expr::new(ExprKind::Num(NumKind::Natural(n)), Span::Artificial);
+    Ctxt::with_new(|cx| {
+        let cache = Cache::at(dir);
+        for i in 0..iterations as u64 {
+            // Built directly because parsing large expressions is slow.
+            let items = (i..i + 1000).map(natural).collect();
+            let expected =
+                Expr::new(ExprKind::NEListLit(items), Span::Artificial);
+            let hash = Hash::SHA256(expected.sha256_hash().unwrap());
+            let typed = Parsed::from_expr_without_imports(expected.clone())
+                .skip_resolve(cx)
+                .unwrap()
+                .typecheck(cx)
+                .unwrap();
+            if let Some(barrier) = barrier {
+                barrier.wait();
+            }
+            // The entry may or may not be there yet.
+            let _ = cache.get(cx, &hash);
+            cache.insert(cx, &hash, &typed).unwrap();
+            // A reader that failed to read the entry must not have deleted it since.
+            let read = cache.get(cx, &hash).unwrap();
+            assert_eq!(read.normalize(cx).to_expr(cx), expected);
+        }
+    })
+}
+
+const HAMMER_CACHE_DIR_VAR: &str = "DHALL_RUST_TEST_HAMMER_CACHE_DIR";
+
+#[test]
+fn cache_concurrent_threads() {
+    use std::sync::{Arc, Barrier};
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-cache-threads-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let barrier = Arc::new(Barrier::new(8));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let dir = dir.clone();
+            let barrier = barrier.clone();
+            std::thread::spawn(move || hammer_cache(&dir, 20, Some(&barrier)))
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    // No temporary files are left behind.
+    assert_eq!(dir.read_dir().unwrap().count(), 20);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Used by `cache_concurrent_processes`, which runs it in child processes.
+#[test]
+fn cache_hammer_child() {
+    if let Some(dir) = std::env::var_os(HAMMER_CACHE_DIR_VAR) {
+        hammer_cache(Path::new(&dir), 20, None);
+    }
+}
+
+#[test]
+fn cache_concurrent_processes() {
+    use std::process::{Command, Stdio};
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-cache-processes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let exe = std::env::current_exe().unwrap();
+    let children: Vec<_> = (0..4)
+        .map(|_| {
+            Command::new(&exe)
+                .arg("cache_hammer_child")
+                .arg("--exact")
+                .arg("--quiet")
+                .env(HAMMER_CACHE_DIR_VAR, &dir)
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    hammer_cache(&dir, 20, None);
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+    assert_eq!(dir.read_dir().unwrap().count(), 20);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cache_binders() {
+    use dhall::semantics::Cache;
+    use dhall::syntax::Hash;
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-cache-binders-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // Entries are keyed by the hash of the alpha-normalized expression.
+    let hash =
+        Hash::SHA256(eval(r"\(_ : Natural) -> _").sha256_hash().unwrap());
+    Ctxt::with_new(|cx| {
+        let typed = Parsed::parse_str(r"\(x : Natural) -> x")
+            .unwrap()
+            .skip_resolve(cx)
+            .unwrap()
+            .typecheck(cx)
+            .unwrap();
+        let cache = Cache::at(&dir);
+        cache.insert(cx, &hash, &typed).unwrap();
+        let read = cache.get(cx, &hash).unwrap();
+        assert_eq!(
+            read.normalize(cx).to_expr(cx),
+            eval(r"\(_ : Natural) -> _")
+        );
+    });
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cache_maintenance() {
+    use dhall::semantics::{Bundle, Cache};
+    use dhall::syntax::binary;
+    use filetime::FileTime;
+    use std::process::Command;
+    use std::time::{Duration, SystemTime};
+
+    let dir = std::env::temp_dir().join(format!(
+        "dhall-rust-cache-maintenance-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = Cache::at(&dir);
+
+    let mut bundle = Bundle::new();
+    let hashes: Vec<_> = ["1", "[2, 3]", "\"four\""]
+        .iter()
+        .map(|s| bundle.insert(binary::encode(&eval(s)).unwrap().into()))
+        .collect();
+    assert_eq!(cache.import(&bundle).unwrap(), 3);
+    assert_eq!(cache.import(&bundle).unwrap(), 0);
+    std::fs::write(dir.join("not-an-entry"), "").unwrap();
+
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 3);
+    assert!(hashes.iter().all(|h| entries.iter().any(|e| &e.hash == h)));
+    assert_eq!(cache.export().unwrap(), bundle);
+    assert!(cache.verify().unwrap().is_empty());
+
+    // Corrupt an entry.
+    std::fs::write(&entries[0].path, "oops").unwrap();
+    let invalid = cache.verify().unwrap();
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].hash, entries[0].hash);
+    assert_eq!(cache.export().unwrap().len(), 2);
+    let cli = || {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dhall"));
+        cmd.arg("cache").arg("--cache-dir").arg(&dir);
+        cmd
+    };
+    let output = cli().arg("verify").output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains(&entries[0].hash.to_string()));
+    assert!(cli()
+        .arg("verify")
+        .arg("--delete")
+        .output()
+        .unwrap()
+        .status
+        .success());
+    assert_eq!(cache.entries().unwrap().len(), 2);
+
+    assert!(cache
+        .prune_older_than(Duration::from_secs(3600))
+        .unwrap()
+        .is_empty());
+    assert_eq!(cache.prune_to_size(u64::MAX).unwrap(), vec![]);
+    assert!(cli()
+        .arg("prune")
+        .arg("--max-size")
+        .arg("0")
+        .output()
+        .unwrap()
+        .status
+        .success());
+    assert!(cache.entries().unwrap().is_empty());
+
+    // Reading from a read-only cache is fine, but it can't be modified.
+    let bundle_file = dir.join("bundle");
+    std::fs::write(&bundle_file, bundle.to_bytes().unwrap()).unwrap();
+    assert!(cli()
+        .arg("import")
+        .arg(&bundle_file)
+        .output()
+        .unwrap()
+        .status
+        .success());
+    let read_only = Cache::at(&dir).read_only(true);
+    assert_eq!(read_only.entries().unwrap().len(), 3);
+    assert!(read_only.prune_to_size(0).is_err());
+    assert!(read_only.import(&Bundle::new()).is_err());
+
+    // Reading an entry counts as an access, even if the filesystem doesn't record it.
+    let two_hours_ago = FileTime::from_system_time(
+        SystemTime::now() - Duration::from_secs(2 * 3600),
+    );
+    for entry in cache.entries().unwrap() {
+        filetime::set_file_times(&entry.path, two_hours_ago, two_hours_ago)
+            .unwrap();
+    }
+    Ctxt::with_new(|cx| assert!(cache.get(cx, &hashes[0]).is_ok()));
+    let removed = cache.prune_older_than(Duration::from_secs(3600)).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(removed.iter().all(|entry| entry.hash != hashes[0]));
+
+    // Sizes and ages that overflow are rejected.
+    for (flag, value) in &[
+        ("--max-age-days", "18446744073709551615"),
+        ("--max-size", "18446744073709551615G"),
+    ] {
+        let output = cli().arg("prune").arg(flag).arg(value).output().unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains(&format!("{} is too large", flag)));
+        assert_eq!(cache.entries().unwrap().len(), 1);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn semi_semantic_cache() {
+    use dhall::semantics::{Cache, ImportEnv};
+    use dhall::syntax::binary;
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-semi-semantic-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let semi_semantic_dir = dir.join("semi-semantic");
+
+    let eval_files = |b: &str, enabled: bool| {
+        let mut fetcher = MapFetcher::default();
+        fetcher
+            .files
+            .insert("/a.dhall".into(), "/b.dhall + 1".into());
+        fetcher.files.insert("/b.dhall".into(), b.into());
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(fetcher)
+                .with_cache(Cache::at(&dir))
+                .semi_semantic_cache(enabled);
+            Ok::<_, Error>(
+                Parsed::parse_str("/a.dhall")?
+                    .resolve_with_env(&mut env)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+        .map_err(|e| e.to_string())
+    };
+
+    assert_eq!(eval_files("1", false), Ok(eval("2")));
+    assert!(!semi_semantic_dir.exists());
+    assert_eq!(eval_files("1", true), Ok(eval("2")));
+    let entries: Vec<_> = semi_semantic_dir
+        .read_dir()
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(entries.len(), 2);
+
+    // Tamper with the entries to check that they get used. The value of `/b.dhall` now comes
+    // from the cache, and since it changed, `/a.dhall` is evaluated again.
+    let data = binary::encode(&eval("42")).unwrap();
+    for path in &entries {
+        std::fs::write(path, &data).unwrap();
+    }
+    assert_eq!(eval_files("1", true), Ok(eval("43")));
+    // Changing a dependency invalidates the entries that depend on it.
+    assert_eq!(eval_files("2", true), Ok(eval("3")));
+
+    // The entries are maintained along with the others, but can't be exported.
+    let cache = Cache::at(&dir);
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 5);
+    assert!(entries.iter().all(|entry| entry.semi_semantic));
+    assert!(cache.verify().unwrap().is_empty());
+    std::fs::write(&entries[0].path, "oops").unwrap();
+    let invalid = cache.verify().unwrap();
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].path, entries[0].path);
+    assert!(cache.export().unwrap().is_empty());
+    assert_eq!(cache.prune_to_size(0).unwrap().len(), 5);
+    assert_eq!(semi_semantic_dir.read_dir().unwrap().count(), 0);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prefetch_independent_imports() {
+    let mut fetcher = MapFetcher::default();
+    for i in 1..=4 {
+        fetcher
+            .urls
+            .insert(format!("https://example.com/{}", i), i.to_string());
+    }
+    fetcher
+        .urls
+        .insert("https://example.com/nested".into(), "./1 + ./2".into());
+    let fetcher = Rc::new(fetcher);
+
+    assert_eq!(
+        eval_with(
+            &fetcher,
+            "https://example.com/1 + https://example.com/2 \
+             + (https://example.com/3 ? https://example.com/4) \
+             + https://example.com/nested"
+        )
+        .unwrap(),
+        eval("9")
+    );
+    // The right side of `?` isn't needed, and the imports of `nested` were already fetched.
+    assert_eq!(
+        *fetcher.batches.borrow(),
+        vec![vec![
+            "https://example.com/1".to_owned(),
+            "https://example.com/2".to_owned(),
+            "https://example.com/3".to_owned(),
+            "https://example.com/nested".to_owned(),
+        ]]
+    );
+}
+
+#[test]
+#[cfg(feature = "reqwest")]
+fn parallel_downloads() {
+    let server = TestServer::new();
+    let origin = server.origin();
+    let mut routes = HashMap::new();
+    routes.insert("/1", Response::new("1"));
+    routes.insert("/2", Response::new("2"));
+    routes.insert("/3", Response::new("3"));
+    server.serve_concurrently(routes, 3);
+
+    // This only succeeds if the three files are downloaded at the same time.
+    assert_eq!(
+        eval_default(&format!("{0}/1 + {0}/2 + {0}/3", origin)).unwrap(),
+        eval("6")
+    );
+}
+
+#[test]
+#[cfg(feature = "async")]
+// `ImportEnv::new` itself isn't generic enough over the lifetime of the context.
+#[allow(clippy::redundant_closure)]
+fn resolve_async() {
+    use dhall::semantics::ImportEnv;
+
+    let server = TestServer::new();
+    let origin = server.origin();
+    let mut routes = HashMap::new();
+    routes.insert("/a.dhall", Response::new("./b.dhall + 1"));
+    routes.insert("/b.dhall", Response::new("./missing.dhall ? 1"));
+    routes.insert("/ill-typed.dhall", Response::new("./b.dhall + True"));
+    routes.insert("/two.dhall", Response::new("2"));
+    routes.insert("/syntax-error.dhall", Response::new("1 +"));
+    server.serve(routes);
+
+    fn assert_send<T: Send>(x: T) -> T {
+        x
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    // The result has to be sent back from the blocking thread pool, and expressions can't be.
+    let eval_async = |source: String| {
+        runtime.block_on(assert_send(Parsed::resolve_async(
+            move || Parsed::parse_str(&source),
+            |cx| ImportEnv::new(cx),
+            |cx, resolved| {
+                Ok::<_, Error>(
+                    resolved
+                        .typecheck(cx)?
+                        .normalize(cx)
+                        .to_expr(cx)
+                        .to_string(),
+                )
+            },
+        )))
+    };
+
+    assert_eq!(
+        eval_async(format!("{}/a.dhall + 1", origin))
+            .unwrap()
+            .unwrap(),
+        "3"
+    );
+    // A type error selects the right side of an alternative too.
+    assert_eq!(
+        eval_async(format!("{0}/ill-typed.dhall ? {0}/two.dhall", origin))
+            .unwrap()
+            .unwrap(),
+        "2"
+    );
+    // Errors that downloading can't fix are reported.
+    let err = eval_async(format!("{}/syntax-error.dhall", origin)).unwrap_err();
+    match err.kind() {
+        ErrorKind::Resolve(ImportError::InImport(_, inner)) => {
+            assert!(matches!(inner.kind(), ErrorKind::Parse(_)), "{}", err)
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+    let err = eval_async(format!("{}/missing.dhall", origin)).unwrap_err();
+    assert!(matches!(
+        import_error(&err),
+        ImportError::HttpStatus { status: 404, .. }
+    ));
+}
+
+#[test]
+fn fn_fetcher() {
+    use dhall::semantics::{resolve_with, FnFetcher};
+
+    let requests = Rc::new(RefCell::new(Vec::new()));
+    let recorded = requests.clone();
+    let fetcher = Rc::new(FnFetcher::new(move |url: &Url, headers: &[_]| {
+        recorded
+            .borrow_mut()
+            .push((url.to_string(), headers.to_vec()));
+        match url.path() {
+            "/a.dhall" => Ok("./b.dhall + 1".to_owned().into()),
+            "/b.dhall" => Ok("41".to_owned().into()),
+            _ => Err(ImportError::HttpStatus {
+                url: url.to_string(),
+                status: 404,
+            }
+            .into()),
+        }
+    }));
+    let eval_with_fetcher = |s: &str| {
+        Ctxt::with_new(|cx| {
+            Ok::<_, Error>(
+                resolve_with(cx, Parsed::parse_str(s)?, fetcher.clone())?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+    };
+    let result = eval_with_fetcher(
+        "https://example.com/a.dhall \
+         using (toMap { Authorization = \"token\" })",
+    );
+    let missing = eval_with_fetcher("https://example.com/missing.dhall");
+
+    assert_eq!(result.unwrap(), eval("42"));
+    assert!(matches!(
+        import_error(&missing.unwrap_err()),
+        ImportError::HttpStatus { status: 404, .. }
+    ));
+    assert_eq!(
+        *requests.borrow(),
+        vec![
+            (
+                "https://example.com/a.dhall".to_owned(),
+                vec![("Authorization".to_owned(), "token".to_owned())]
+            ),
+            // Relative imports inherit the headers.
+            (
+                "https://example.com/b.dhall".to_owned(),
+                vec![("Authorization".to_owned(), "token".to_owned())]
+            ),
+            ("https://example.com/missing.dhall".to_owned(), vec![]),
+        ]
+    );
+}
+
+#[test]
+fn import_graph() {
+    use dhall::syntax::ImportMode;
+
+    let mut fetcher = MapFetcher::default();
+    fetcher.files.insert(
+        "/virtual/a.dhall".into(),
+        "{ n = ./b.dhall + (./missing.dhall ? ./c.dhall) \
+         + (./c.dhall ? ./never.dhall), t = ./b.dhall as Text }"
+            .into(),
+    );
+    fetcher.files.insert("/virtual/b.dhall".into(), "1".into());
+    fetcher
+        .files
+        .insert("/virtual/c.dhall".into(), "./b.dhall + 1".into());
+    let fetcher = Rc::new(fetcher);
+
+    Ctxt::with_new(|cx| {
+        let resolved = Parsed::parse_str("/virtual/a.dhall")
+            .unwrap()
+            .resolve_with(cx, fetcher.clone())
+            .unwrap();
+        let graph = resolved.import_graph(cx);
+
+        let nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.location
+                        .local_path()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                    node.location.mode(),
+                    node.read,
+                )
+            })
+            .collect();
+        let local = |path: &str, mode| (Some(path.to_owned()), mode, true);
+        assert_eq!(
+            nodes,
+            vec![
+                (None, ImportMode::Code, true),
+                local("/virtual/a.dhall", ImportMode::Code),
+                local("/virtual/b.dhall", ImportMode::Code),
+                (
+                    Some("/virtual/missing.dhall".to_owned()),
+                    ImportMode::Code,
+                    false
+                ),
+                local("/virtual/c.dhall", ImportMode::Code),
+                local("/virtual/b.dhall", ImportMode::RawText),
+            ]
+        );
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.resolved))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 1, true),
+                (1, 2, true),
+                (1, 3, false),
+                (1, 4, true),
+                (4, 2, true),
+                (1, 4, true),
+                (1, 5, true),
+            ]
+        );
+        assert_eq!(graph.imports_of(4).collect::<Vec<_>>(), vec![2]);
+
+        // `./never.dhall` wasn't needed.
+        assert_eq!(graph.alternatives.len(), 2);
+        assert_eq!(graph.alternatives[0].file, 1);
+        assert_eq!(graph.alternatives[0].left, vec![2]);
+        assert_eq!(graph.alternatives[0].right, vec![3]);
+        assert!(!graph.alternatives[0].selected_left);
+        assert_eq!(graph.alternatives[1].left, vec![5]);
+        assert!(graph.alternatives[1].right.is_empty());
+        assert!(graph.alternatives[1].selected_left);
+    });
+}
+
+#[test]
+fn depfile() {
+    use std::process::Command;
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-depfile-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let main = dir.join("my config.dhall");
+    std::fs::write(
+        &main,
+        "{ a = ./lib.dhall, b = ./lib.dhall as Text, \
+         c = ./missing.dhall ? 1, d = env:DHALL_DEPFILE_TEST ? 2 }",
+    )
+    .unwrap();
+    std::fs::write(dir.join("lib.dhall"), "1").unwrap();
+    let main_escaped = main.to_string_lossy().replace(' ', "\\ ");
+    let lib = dir.join("lib.dhall");
+    let lib = lib.to_string_lossy();
+
+    Ctxt::with_new(|cx| {
+        let graph = Parsed::parse_file(&main)
+            .unwrap()
+            .resolve(cx)
+            .unwrap()
+            .import_graph(cx);
+        assert_eq!(
+            graph.to_depfile("out/config.yaml"),
+            format!("out/config.yaml: \\\n  {} \\\n  {}\n", main_escaped, lib)
+        );
+    });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dhall"))
+        .arg("depfile")
+        .arg("--env-vars")
+        .arg(&main)
+        .arg("$out")
+        .env("DHALL_DEPFILE_TEST", "3")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "$$out: \\\n  {} \\\n  {}\n# env:DHALL_DEPFILE_TEST\n",
+            main_escaped, lib
+        )
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn freeze() {
+    use dhall::semantics::{Cache, FreezeOptions, ImportEnv};
+    use dhall::syntax::Hash;
+    use std::process::Command;
+
+    let mut fetcher = MapFetcher::default();
+    fetcher.urls.insert(
+        "https://example.com/id.dhall".into(),
+        "λ(x : Natural) → x".into(),
+    );
+    fetcher.files.insert("/virtual/b.dhall".into(), "1".into());
+    let fetcher = Rc::new(fetcher);
+    let hash_of = |s: &str| Hash::SHA256(eval(s).sha256_hash().unwrap());
+    let id_hash = hash_of("λ(_ : Natural) → _");
+    let one_hash = hash_of("1");
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-freeze-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let source = "{ f = https://example.com/id.dhall, b = /virtual/b.dhall, \
+                  c = https://example.com/missing.dhall ? 2, \
+                  t = https://example.com/id.dhall as Text }";
+    let freeze = |options: FreezeOptions| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(fetcher.clone())
+                .with_cache(Cache::at(&dir));
+            let frozen = Parsed::parse_str(source)
+                .unwrap()
+                .freeze_with_env(&mut env, options)
+                .unwrap();
+            // The hashes are correct.
+            frozen.clone().resolve_with(cx, fetcher.clone()).unwrap();
+            frozen.to_expr()
+        })
+    };
+
+    assert_eq!(
+        freeze(FreezeOptions::default().cache(false)),
+        eval(&format!(
+            "{{ f = https://example.com/id.dhall {}, b = /virtual/b.dhall, \
+             c = https://example.com/missing.dhall ? 2, \
+             t = https://example.com/id.dhall as Text }}",
+            id_hash
+        ))
+    );
+    assert!(!Cache::at(&dir).contains(&id_hash));
+
+    assert_eq!(
+        freeze(FreezeOptions::default().all_imports(true)),
+        eval(&format!(
+            "{{ f = https://example.com/id.dhall {}, b = /virtual/b.dhall {}, \
+             c = https://example.com/missing.dhall ? 2, \
+             t = https://example.com/id.dhall as Text }}",
+            id_hash, one_hash
+        ))
+    );
+    assert!(Cache::at(&dir).contains(&id_hash));
+    assert!(Cache::at(&dir).contains(&one_hash));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The hashes are inserted in the source, including on the side of a `?` that resolution
+    // didn't need.
+    let source = "-- comment\n{ f = https://example.com/id.dhall\n, \
+                  g = /virtual/b.dhall ? https://example.com/id.dhall }";
+    let frozen = Ctxt::with_new(|cx| {
+        let mut env = ImportEnv::new(cx).with_fetcher(fetcher.clone());
+        Parsed::parse_str(source)
+            .unwrap()
+            .freeze_source_with_env(
+                &mut env,
+                FreezeOptions::default().all_imports(true).cache(false),
+            )
+            .unwrap()
+    });
+    assert_eq!(
+        frozen,
+        format!(
+            "-- comment\n{{ f = https://example.com/id.dhall {0}\n, \
+             g = /virtual/b.dhall {1} ? https://example.com/id.dhall {0} }}",
+            id_hash, one_hash
+        )
+    );
+
+    // The hash of an import with headers doesn't apply to the headers.
+    let mut fetcher = MapFetcher {
+        token: Some("Bearer secret".into()),
+        ..MapFetcher::default()
+    };
+    fetcher
+        .urls
+        .insert("https://example.com/b.dhall".into(), "1".into());
+    let headers =
+        r#"[ { mapKey = "Authorization", mapValue = "Bearer secret" } ]"#;
+    fetcher
+        .files
+        .insert("/virtual/headers.dhall".into(), headers.into());
+    let fetcher = Rc::new(fetcher);
+    let headers_hash = hash_of(headers);
+    let freeze_source = |source: &str, options: FreezeOptions| {
+        let frozen = Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx).with_fetcher(fetcher.clone());
+            Parsed::parse_str(source)
+                .unwrap()
+                .freeze_source_with_env(&mut env, options.cache(false))
+                .unwrap()
+        });
+        assert_eq!(eval_with(&fetcher, &frozen).unwrap(), eval("1"));
+        frozen
+    };
+    let source = "https://example.com/b.dhall using /virtual/headers.dhall";
+    assert_eq!(
+        freeze_source(source, FreezeOptions::default()),
+        format!(
+            "https://example.com/b.dhall using (/virtual/headers.dhall) {}",
+            one_hash
+        )
+    );
+    assert_eq!(
+        freeze_source(source, FreezeOptions::default().all_imports(true)),
+        format!(
+            "https://example.com/b.dhall using (/virtual/headers.dhall {}) {}",
+            headers_hash, one_hash
+        )
+    );
+    assert_eq!(
+        freeze_source(
+            "https://example.com/b.dhall using (/virtual/headers.dhall)",
+            FreezeOptions::default()
+        ),
+        format!(
+            "https://example.com/b.dhall using (/virtual/headers.dhall) {}",
+            one_hash
+        )
+    );
+    let frozen = Ctxt::with_new(|cx| {
+        let mut env = ImportEnv::new(cx).with_fetcher(fetcher.clone());
+        Parsed::parse_str(source)
+            .unwrap()
+            .freeze_with_env(&mut env, FreezeOptions::default().cache(false))
+            .unwrap()
+            .to_expr()
+    });
+    assert_eq!(eval_with(&fetcher, &frozen.to_string()).unwrap(), eval("1"));
+
+    // A failed import outside of an alternative is an error.
+    Ctxt::with_new(|cx| {
+        let parsed = Parsed::parse_str("https://example.com/missing.dhall");
+        assert!(parsed
+            .unwrap()
+            .freeze_with_env(
+                &mut ImportEnv::new(cx).with_fetcher(fetcher.clone()),
+                FreezeOptions::default()
+            )
+            .is_err());
+    });
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-freeze-cli-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("main.dhall");
+    std::fs::write(&file, "-- The library\n./lib.dhall  + 1\n").unwrap();
+    std::fs::write(dir.join("lib.dhall"), "1").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dhall"))
+        .arg("freeze")
+        .arg("--all")
+        .arg("--no-cache")
+        .arg("--inplace")
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        format!("-- The library\n./lib.dhall {}  + 1\n", one_hash)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lockfile() {
+    use dhall::semantics::{Cache, ImportEnv, Lockfile, LockfileMode};
+    use dhall::syntax::Hash;
+
+    let fetcher_with = |a: &str| {
+        let mut fetcher = MapFetcher::default();
+        fetcher
+            .urls
+            .insert("https://example.com/a.dhall".into(), a.into());
+        fetcher.urls.insert(
+            "https://example.com/b.dhall".into(),
+            "./a.dhall + 1".into(),
+        );
+        Rc::new(fetcher)
+    };
+    let hash_of = |s: &str| Hash::SHA256(eval(s).sha256_hash().unwrap());
+    let a_url = Url::parse("https://example.com/a.dhall").unwrap();
+    let b_url = Url::parse("https://example.com/b.dhall").unwrap();
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-lockfile-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let resolve = |fetcher: &Rc<MapFetcher>,
+                   lockfile: Lockfile,
+                   mode: LockfileMode,
+                   cache: bool,
+                   source: &str| {
+        Ctxt::with_new(|cx| {
+            let env = ImportEnv::new(cx)
+                .with_fetcher(fetcher.clone())
+                .with_lockfile(lockfile, mode);
+            let mut env = if cache {
+                env.with_cache(Cache::at(&dir)).offline(true)
+            } else {
+                env.without_cache()
+            };
+            let res = Parsed::parse_str(source)
+                .and_then(|parsed| parsed.resolve_with_env(&mut env))
+                .and_then(|resolved| Ok(resolved.typecheck(cx)?))
+                .map(|typed| typed.normalize(cx).to_expr(cx));
+            (res, env.into_lockfile().unwrap())
+        })
+    };
+    let source = "https://example.com/b.dhall";
+
+    // Generate the lockfile, adding the values to the cache.
+    let old = fetcher_with("1");
+    let (res, lockfile) = Ctxt::with_new(|cx| {
+        let mut env = ImportEnv::new(cx)
+            .with_fetcher(old.clone())
+            .with_cache(Cache::at(&dir))
+            .with_lockfile(Lockfile::new(), LockfileMode::Update);
+        let res = Parsed::parse_str(source)
+            .unwrap()
+            .resolve_with_env(&mut env);
+        (res.is_ok(), env.into_lockfile().unwrap())
+    });
+    assert!(res);
+    assert_eq!(
+        lockfile.iter().collect::<Vec<_>>(),
+        vec![(&a_url, &hash_of("1")), (&b_url, &hash_of("2"))]
+    );
+    assert_eq!(lockfile.to_string().parse::<Lockfile>().unwrap(), lockfile);
+    assert!(Cache::at(&dir).contains(&hash_of("1")));
+
+    // The locked values are used even though the upstream contents changed.
+    let new = fetcher_with("10");
+    let (res, _) =
+        resolve(&new, lockfile.clone(), LockfileMode::Check, true, source);
+    assert_eq!(res.unwrap(), eval("2"));
+
+    // Without the cache, the change is detected.
+    let (res, _) =
+        resolve(&new, lockfile.clone(), LockfileMode::Check, false, source);
+    assert!(res.unwrap_err().to_string().contains("hash mismatch"));
+
+    // Checking fails on unlocked imports, and updating adds them.
+    let mut partial = lockfile.clone();
+    partial.remove(&a_url);
+    let (res, _) =
+        resolve(&old, partial.clone(), LockfileMode::Check, false, source);
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .contains("is not in the lockfile"));
+    let (res, updated) =
+        resolve(&old, partial, LockfileMode::Update, false, source);
+    assert_eq!(res.unwrap(), eval("2"));
+    assert_eq!(updated, lockfile);
+
+    // Refreshing accepts the new contents.
+    let (res, refreshed) =
+        resolve(&new, lockfile, LockfileMode::Refresh, false, source);
+    assert_eq!(res.unwrap(), eval("11"));
+    assert_eq!(refreshed.get(&a_url), Some(&hash_of("10")));
+    assert_eq!(refreshed.get(&b_url), Some(&hash_of("11")));
+
+    assert!("https://example.com/a.dhall sha256:00"
+        .parse::<Lockfile>()
+        .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn import_chain_errors() {
+    use dhall::error::ErrorKind;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Error>();
+
+    let mut fetcher = MapFetcher::default();
+    fetcher
+        .files
+        .insert("/virtual/a.dhall".into(), "{ x = ./b.dhall }".into());
+    fetcher
+        .files
+        .insert("/virtual/b.dhall".into(), "1 + ./c.dhall".into());
+    let fetcher = Rc::new(fetcher);
+
+    let err = eval_with(&fetcher, "/virtual/a.dhall").unwrap_err();
+    let (stack, inner) = match err.kind() {
+        ErrorKind::Resolve(ImportError::InImport(stack, inner)) => {
+            (stack, inner)
+        }
+        _ => panic!("unexpected error: {}", err),
+    };
+    let locations: Vec<_> = stack
+        .iter()
+        .map(|frame| frame.location.to_string())
+        .collect();
+    assert_eq!(
+        locations,
+        vec!["/virtual/a.dhall", "/virtual/b.dhall", "/virtual/c.dhall"]
+    );
+    assert!(matches!(inner.kind(), ErrorKind::IO(_)));
+    assert!(err.to_string().starts_with(
+        "↳ /virtual/a.dhall\n  ↳ /virtual/b.dhall\n    ↳ /virtual/c.dhall\n\n"
+    ));
+    // The innermost import is shown in the file that imports it.
+    assert!(err.to_string().contains("1 + ./c.dhall"));
+    // The outer imports are shown too.
+    assert!(err.to_string().contains(
+        "note: while importing /virtual/b.dhall\n --> <current file>:1:7\n  |\n1 | { x = ./b.dhall }"
+    ));
+    assert!(err
+        .to_string()
+        .contains("note: while importing /virtual/a.dhall"));
+}
+
+#[test]
+fn import_alternative_errors() {
+    use dhall::error::ErrorKind;
+
+    let mut fetcher = MapFetcher::default();
+    fetcher.files.insert(
+        "/virtual/a.dhall".into(),
+        "./typo.dhall ? ./b.dhall ? env:DHALL_TEST_UNSET".into(),
+    );
+    fetcher
+        .files
+        .insert("/virtual/b.dhall".into(), "1 + True".into());
+    let fetcher = Rc::new(fetcher);
+
+    let err = eval_with(&fetcher, "/virtual/a.dhall").unwrap_err();
+    let err = match err.kind() {
+        ErrorKind::Resolve(ImportError::InImport(_, inner)) => inner,
+        _ => panic!("unexpected error: {}", err),
+    };
+    let failed = match err.kind() {
+        ErrorKind::Resolve(ImportError::AlternativesFailed(failed)) => failed,
+        _ => panic!("unexpected error: {}", err),
+    };
+    // Nested alternatives are flattened.
+    assert_eq!(failed.len(), 3);
+    let msg = err.to_string();
+    assert!(msg.starts_with("every alternative of `?` failed"));
+    assert!(msg.contains("↳ /virtual/typo.dhall"));
+    assert!(msg.contains("↳ /virtual/b.dhall"));
+    assert!(msg.contains("↳ env:DHALL_TEST_UNSET"));
+}
+
+#[test]
+fn virtual_files() {
+    use dhall::semantics::{resolve_with, DefaultFetcher, VirtualFiles};
+
+    let files = VirtualFiles::new()
+        .with_file(
+            "project/config/main.dhall",
+            "{ port = ./port.dhall, name = ../name.txt as Text }",
+        )
+        .with_file("project/config/port.dhall", "8080")
+        .with_file("./project/name.txt", "preview")
+        .with_file("/etc/broken.dhall", "./missing.dhall");
+    assert_eq!(files.len(), 4);
+    assert_eq!(files.get("project/./config/../name.txt"), Some("preview"));
+
+    let eval_file = |path: &str| -> Result<Expr, Error> {
+        Ctxt::with_new(|cx| {
+            Ok(Parsed::parse_file_with(Path::new(path), &files)?
+                .resolve_with(cx, files.clone())?
+                .typecheck(cx)?
+                .normalize(cx)
+                .to_expr(cx))
+        })
+    };
+    assert_eq!(
+        eval_file("project/config/main.dhall").unwrap(),
+        eval(r#"{ port = 8080, name = "preview" }"#)
+    );
+    assert_eq!(
+        eval_file("./project/config/../config/port.dhall").unwrap(),
+        eval("8080")
+    );
+
+    let err = eval_file("/etc/broken.dhall").unwrap_err();
+    assert!(err.to_string().contains("/etc/missing.dhall"));
+    assert!(eval_file("project/main.dhall").is_err());
+
+    // Nothing else is accessed, unless there's a fallback.
+    std::env::set_var("DHALL_TEST_VIRTUAL_FILES", "1");
+    let eval_with = |files: VirtualFiles, source: &str| {
+        Ctxt::with_new(|cx| {
+            Ok::<_, Error>(
+                resolve_with(cx, Parsed::parse_str(source)?, files)?
+                    .typecheck(cx)?
+                    .normalize(cx)
+                    .to_expr(cx),
+            )
+        })
+    };
+    let files = VirtualFiles::new().with_env_var("DHALL_TEST_PORT", "8080");
+    assert_eq!(
+        eval_with(files.clone(), "env:DHALL_TEST_PORT").unwrap(),
+        eval("8080")
+    );
+    assert!(eval_with(files.clone(), "env:DHALL_TEST_VIRTUAL_FILES").is_err());
+    assert!(eval_with(files.clone(), "https://example.com/a.dhall").is_err());
+    let files = files.with_fallback(DefaultFetcher);
+    assert_eq!(
+        eval_with(files, "env:DHALL_TEST_VIRTUAL_FILES").unwrap(),
+        eval("1")
+    );
+}
+
+const VIRTUAL_FILES_CACHE_VAR: &str = "DHALL_RUST_TEST_VIRTUAL_FILES_CACHE";
+
+/// Used by `virtual_files_without_cache`, which runs it in a child process with its own
+/// `XDG_CACHE_HOME`.
+#[test]
+fn virtual_files_without_cache_child() {
+    use dhall::semantics::{resolve_with, VirtualFiles};
+
+    if std::env::var_os(VIRTUAL_FILES_CACHE_VAR).is_none() {
+        return;
+    }
+    let sha = eval("1").sha256_hash().unwrap();
+    let source = format!("/virtual/one.dhall sha256:{}", hex::encode(sha));
+    let files = VirtualFiles::new().with_file("/virtual/one.dhall", "1");
+    Ctxt::with_new(|cx| {
+        let parsed = Parsed::parse_str(&source).unwrap();
+        resolve_with(cx, parsed, files).unwrap();
+    });
+}
+
+#[test]
+fn virtual_files_without_cache() {
+    use std::process::Command;
+
+    let dir = std::env::temp_dir()
+        .join(format!("dhall-rust-virtual-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache_dir = dir.join("dhall");
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    // An invalid entry would be deleted and replaced if the cache was used.
+    let sha = eval("1").sha256_hash().unwrap();
+    let entry = cache_dir.join(format!("1220{}", hex::encode(sha)));
+    std::fs::write(&entry, "invalid").unwrap();
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .arg("virtual_files_without_cache_child")
+        .arg("--exact")
+        .arg("--quiet")
+        .env(VIRTUAL_FILES_CACHE_VAR, "1")
+        .env("XDG_CACHE_HOME", &dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&entry).unwrap(), "invalid");
+    assert_eq!(cache_dir.read_dir().unwrap().count(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn standard_version_of_imports() {
+    use dhall::semantics::{Cache, ImportEnv, VirtualFiles};
+    use dhall::standard::StandardVersion;
+    use dhall::syntax::Hash;
+
+    let files = VirtualFiles::new()
+        .with_file("main.dhall", "{ shape = ./shape.dhall }")
+        .with_file("shape.dhall", "showConstructor < Circle | Square >.Circle");
+    let resolve = |version| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(files.clone())
+                .with_standard_version(version);
+            Parsed::parse_file_with(Path::new("main.dhall"), &files)?
+                .resolve_with_env(&mut env)
+                .map(|_| ())
+        })
+    };
+    assert!(resolve(StandardVersion::new(22, 0, 0)).is_ok());
+    let err = resolve(StandardVersion::new(21, 0, 0)).unwrap_err();
+    let err = err.to_string();
+    assert!(err.contains("↳ ./shape.dhall"), "{}", err);
+    assert!(err.contains("`showConstructor`"), "{}", err);
+
+    // Imports from the cache are checked too.
+    let dir = std::env::temp_dir().join(format!(
+        "dhall-rust-standard-version-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let hash = Hash::SHA256(eval("Text/replace").sha256_hash().unwrap());
+    Ctxt::with_new(|cx| {
+        let typed = Parsed::parse_str("Text/replace")
+            .unwrap()
+            .skip_resolve(cx)
+            .unwrap()
+            .typecheck(cx)
+            .unwrap();
+        Cache::at(&dir).insert(cx, &hash, &typed).unwrap();
+    });
+    let resolve_cached = |version| {
+        Ctxt::with_new(|cx| {
+            let mut env = ImportEnv::new(cx)
+                .with_fetcher(VirtualFiles::new())
+                .with_cache(Cache::at(&dir))
+                .with_standard_version(version);
+            Parsed::parse_str(&format!("missing {}", hash))?
+                .resolve_with_env(&mut env)
+                .map(|_| ())
+        })
+    };
+    assert!(resolve_cached(StandardVersion::new(19, 0, 0)).is_ok());
+    let err = resolve_cached(StandardVersion::new(18, 0, 0)).unwrap_err();
+    assert!(err.to_string().contains("`Text/replace`"), "{}", err);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
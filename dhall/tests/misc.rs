@@ -48,3 +48,36 @@ fn manual_function_application() {
     // The crate uses essentially a global context, created here.
     Ctxt::with_new(run).unwrap();
 }
+
+#[test]
+fn standard_version() {
+    use dhall::standard::{Feature, StandardVersion};
+
+    // Features are listed in the order they were added.
+    let versions: Vec<_> = Feature::ALL.iter().map(|f| f.since()).collect();
+    assert!(versions.windows(2).all(|w| w[0] <= w[1]));
+    assert!(STANDARD_VERSION.has_feature(Feature::TextReplace));
+    assert!(!STANDARD_VERSION.has_feature(Feature::Bytes));
+    assert!(!StandardVersion::new(99, 0, 0).has_feature(Feature::WithOptional));
+    assert_eq!("v19.0.0".parse(), Ok(STANDARD_VERSION));
+    assert!("19.0".parse::<StandardVersion>().is_err());
+
+    let check = |s: &str, version| {
+        Parsed::parse_str(s)
+            .unwrap()
+            .check_standard_version(version)
+    };
+    let v18 = StandardVersion::new(18, 0, 0);
+    assert!(check("Natural/subtract 1 2", v18).is_ok());
+    let err = check(r#"{ a = 1, b = Text/replace "a" "b" "c" }"#, v18)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(
+        "`Text/replace` (added in version 19.0.0 of the standard) cannot be \
+         used with version 18.0.0"
+    ));
+    assert!(check(r#"0x"00""#, STANDARD_VERSION).is_err());
+    assert!(
+        check("2021-01-31T12:00:00Z", StandardVersion::new(21, 0, 0)).is_ok()
+    );
+}
@@ -2,7 +2,6 @@ use anyhow::Result;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use std::env;
-use std::ffi::OsString;
 use std::fmt::{Debug, Display};
 use std::fs::{create_dir_all, read_to_string, File};
 use std::io::{Read, Write};
@@ -103,7 +102,7 @@ impl TestFile {
             TestFile::Source(_) => Parsed::parse_file(&self.path())?,
             TestFile::Binary(_) => Parsed::parse_binary_file(&self.path())?,
             TestFile::UI(_) => {
-                Err(TestError(format!("Can't parse a UI test file")))?
+                Err(TestError("Can't parse a UI test file".to_string()))?
             }
         })
     }
@@ -138,9 +137,9 @@ impl TestFile {
                 let expr_data = binary::encode(&expr)?;
                 file.write_all(&expr_data)?;
             }
-            TestFile::UI(_) => Err(TestError(format!(
-                "Can't write an expression to a UI file"
-            )))?,
+            TestFile::UI(_) => Err(TestError(
+                "Can't write an expression to a UI file".to_string(),
+            ))?,
         }
         Ok(())
     }
@@ -148,9 +147,9 @@ impl TestFile {
     fn write_ui(&self, x: impl Display) -> Result<()> {
         match self {
             TestFile::UI(_) => {}
-            _ => Err(TestError(format!(
-                "Can't write a ui string to a dhall file"
-            )))?,
+            _ => Err(TestError(
+                "Can't write a ui string to a dhall file".to_string(),
+            ))?,
         }
         let path = self.path();
         create_dir_all(path.parent().unwrap())?;
@@ -195,7 +194,7 @@ impl TestFile {
     pub fn compare_binary(&self, expr: Expr) -> Result<()> {
         match self {
             TestFile::Binary(_) => {}
-            _ => Err(TestError(format!("This is not a binary file")))?,
+            _ => Err(TestError("This is not a binary file".to_string()))?,
         }
         if !self.path().is_file() {
             return self.write_expr(expr);
@@ -204,7 +203,7 @@ impl TestFile {
         let expr_data = binary::encode(&expr)?;
         let expected_data = {
             let mut data = Vec::new();
-            File::open(&self.path())?.read_to_end(&mut data)?;
+            File::open(self.path())?.read_to_end(&mut data)?;
             data
         };
 
@@ -277,6 +276,7 @@ enum SpecTestKind {
     TypeInferenceSuccess,
     TypeInferenceFailure,
     Normalization,
+    NormalizationFailure,
     AlphaNormalization,
 }
 
@@ -308,7 +308,7 @@ fn dhall_files_in_dir<'a>(
         .filter_map(move |path| {
             let path = path.path().strip_prefix(dir).unwrap();
             let ext = path.extension()?;
-            if *ext != OsString::from(filetype.to_ext()) {
+            if ext != filetype.to_ext() {
                 return None;
             }
             let path = path.to_string_lossy();
@@ -330,7 +330,7 @@ static UPDATE_TEST_FILES: AtomicBool = AtomicBool::new(false);
 static LOCAL_TEST_PATH: &str = "tests/";
 static TEST_PATHS: &[&str] = &["../dhall-lang/tests/", LOCAL_TEST_PATH];
 
-static FEATURES: &'static [TestFeature] = &[
+static FEATURES: &[TestFeature] = &[
     TestFeature {
         module_name: "parser_success",
         directory: "parser/success/",
@@ -401,6 +401,13 @@ static FEATURES: &'static [TestFeature] = &[
         input_type: FileType::Text,
         output_type: FileType::Text,
     },
+    TestFeature {
+        module_name: "beta_normalize_failure",
+        directory: "normalization/failure/",
+        variant: SpecTestKind::NormalizationFailure,
+        input_type: FileType::Text,
+        output_type: FileType::UI,
+    },
     TestFeature {
         module_name: "alpha_normalize",
         directory: "alpha-normalization/success/",
@@ -495,7 +502,7 @@ fn ignore_test(variant: SpecTestKind, path: &str) -> bool {
 
     // This will never succeed because of a specificity of dhall-rust.
     let is_meant_to_fail = false
-        // We don't support bignums
+        // Numbers are limited to 64 bits
         || path == "binary-decode/success/unit/IntegerBigNegative"
         || path == "binary-decode/success/unit/IntegerBigPositive"
         || path == "binary-decode/success/unit/NaturalBig"
@@ -593,8 +600,8 @@ fn run_test(test: &SpecTest) -> Result<()> {
             ParserFailure => {
                 use std::io;
                 let err = unwrap_err(expr.parse())?;
-                match err.downcast_ref::<DhallError>() {
-                    Some(err) => match err.kind() {
+                if let Some(err) = err.downcast_ref::<DhallError>() {
+                    match err.kind() {
                         ErrorKind::Parse(_) => {}
                         ErrorKind::IO(e)
                             if e.kind() == io::ErrorKind::InvalidData => {}
@@ -602,8 +609,7 @@ fn run_test(test: &SpecTest) -> Result<()> {
                             "Expected parse error, got: {:?}",
                             e
                         )))?,
-                    },
-                    None => {}
+                    }
                 }
                 expected.compare_ui(err)?;
             }
@@ -651,6 +657,11 @@ fn run_test(test: &SpecTest) -> Result<()> {
                 let expr = expr.normalize(cx)?;
                 expected.compare(expr.to_expr(cx))?;
             }
+            NormalizationFailure => {
+                let err =
+                    unwrap_err(expr.typecheck(cx)?.normalize_checked(cx))?;
+                expected.compare_ui(err)?;
+            }
             AlphaNormalization => {
                 let expr = expr.normalize(cx)?.to_expr_alpha(cx);
                 expected.compare(expr)?;
@@ -131,6 +131,10 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
             Num(Natural(x)) => visitor.visit_u64(*x),
             Num(Integer(x)) => visitor.visit_i64(*x),
             Num(Double(x)) => visitor.visit_f64((*x).into()),
+            Num(Date(x)) => visitor.visit_string(x.to_string()),
+            Num(Time(x)) => visitor.visit_string(x.to_string()),
+            Num(TimeZone(x)) => visitor.visit_string(x.to_string()),
+            Num(Bytes(x)) => visitor.visit_bytes(x),
             Text(x) => visitor.visit_str(x),
             List(xs) => {
                 visitor.visit_seq(SeqDeserializer::new(xs.iter().map(val)))
@@ -138,7 +142,7 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
             Optional(None) => visitor.visit_none(),
             Optional(Some(x)) => visitor.visit_some(val(x)),
             Record(m) => visitor.visit_map(MapDeserializer::new(
-                m.iter().map(|(k, v)| (k.as_ref(), val(v))),
+                m.iter().map(|(k, v)| (k.as_str(), val(v))),
             )),
             Union(field_name, Some(x)) => visitor.visit_enum(
                 MapAccessDeserializer::new(MapDeserializer::new(
@@ -164,8 +168,22 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
         let val = |x| Deserializer(Cow::Borrowed(x));
         match self.0.as_ref() {
             // Blindly takes keys in sorted order.
-            SimpleValue::Record(m) => visitor
-                .visit_seq(SeqDeserializer::new(m.iter().map(|(_, v)| val(v)))),
+            SimpleValue::Record(m) => {
+                visitor.visit_seq(SeqDeserializer::new(m.values().map(val)))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0.as_ref() {
+            // So that `Vec<u8>` can be deserialized from `Bytes`.
+            SimpleValue::Num(NumKind::Bytes(x)) => {
+                visitor.visit_seq(SeqDeserializer::new(x.iter().copied()))
+            }
             _ => self.deserialize_any(visitor),
         }
     }
@@ -182,8 +200,8 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
 
     serde::forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit_struct newtype_struct seq
-        tuple_struct map struct enum identifier ignored_any
+        bytes byte_buf option unit_struct newtype_struct tuple_struct map
+        struct enum identifier ignored_any
     }
 }
 
@@ -220,6 +238,14 @@ impl<'de> serde::de::Visitor<'de> for SimpleValueVisitor {
         Ok(SimpleValue::Text(value))
     }
 
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<SimpleValue, E> {
+        Ok(SimpleValue::Num(NumKind::Bytes(value.to_owned())))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<SimpleValue, E> {
+        Ok(SimpleValue::Num(NumKind::Bytes(value)))
+    }
+
     fn visit_none<E>(self) -> Result<SimpleValue, E> {
         Ok(SimpleValue::Optional(None))
     }
@@ -269,6 +269,9 @@ mod value;
 #[doc(hidden)]
 pub use dhall_proc_macros::StaticType;
 
+pub use dhall::semantics::ImportPolicy;
+pub use dhall::standard::StandardVersion;
+
 pub use deserialize::{from_simple_value, FromDhall};
 pub(crate) use error::ErrorKind;
 pub use error::{Error, Result};
@@ -276,4 +279,6 @@ pub use options::de::{from_binary_file, from_file, from_str, Deserializer};
 pub use options::ser::{serialize, Serializer};
 pub use serialize::ToDhall;
 pub use static_type::StaticType;
-pub use value::{NumKind, SimpleType, SimpleValue, Value};
+pub use value::{
+    DateLit, NumKind, SimpleType, SimpleValue, TimeLit, TimeZoneLit, Value,
+};
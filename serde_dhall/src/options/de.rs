@@ -1,6 +1,12 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
 
-use dhall::{Ctxt, Parsed};
+use dhall::semantics::{Cache, FnFetcher, ImportEnv, ImportPolicy, RemoteFile};
+use dhall::standard::StandardVersion;
+use dhall::{Ctxt, Parsed, Resolved};
 
 use crate::options::{HasAnnot, ManualAnnot, NoAnnot, StaticAnnot, TypeAnnot};
 use crate::SimpleType;
@@ -8,12 +14,41 @@ use crate::{Error, ErrorKind, FromDhall, Result, Value};
 
 #[derive(Debug, Clone)]
 enum Source<'a> {
-    Str(&'a str),
+    Str(Cow<'a, str>),
     File(PathBuf),
     BinaryFile(PathBuf),
     // Url(&'a str),
 }
 
+/// `Send + Sync`, so that `parse_async` can use the fetcher on other threads. On wasm, where there
+/// is no `parse_async`, fetchers usually hold JavaScript values, which are neither.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSendSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSendSync for T {}
+
+#[cfg(not(target_arch = "wasm32"))]
+type RemoteFetchFn = dyn Fn(&Url, &[(String, String)]) -> dhall::error::Result<RemoteFile>
+    + Send
+    + Sync;
+#[cfg(target_arch = "wasm32")]
+type RemoteFetchFn =
+    dyn Fn(&Url, &[(String, String)]) -> dhall::error::Result<RemoteFile>;
+
+/// The function set with `Deserializer::remote_fetcher`.
+#[derive(Clone)]
+struct RemoteFetcher(Arc<RemoteFetchFn>);
+
+impl fmt::Debug for RemoteFetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RemoteFetcher").finish()
+    }
+}
+
 /// Controls how a Dhall value is read.
 ///
 /// This builder exposes the ability to configure how a value is deserialized and what operations
@@ -58,8 +93,14 @@ pub struct Deserializer<'a, A> {
     source: Source<'a>,
     annot: A,
     allow_imports: bool,
-    // allow_remote_imports: bool,
-    // use_cache: bool,
+    import_policy: ImportPolicy,
+    offline: bool,
+    use_cache: bool,
+    cache_dir: Option<PathBuf>,
+    read_only_cache: bool,
+    semi_semantic_cache: bool,
+    standard_version: Option<StandardVersion>,
+    remote_fetcher: Option<RemoteFetcher>,
 }
 
 impl<'a> Deserializer<'a, NoAnnot> {
@@ -68,12 +109,18 @@ impl<'a> Deserializer<'a, NoAnnot> {
             source,
             annot: NoAnnot,
             allow_imports: true,
-            // allow_remote_imports: true,
-            // use_cache: true,
+            import_policy: ImportPolicy::default(),
+            offline: false,
+            use_cache: true,
+            cache_dir: None,
+            read_only_cache: false,
+            semi_semantic_cache: false,
+            standard_version: None,
+            remote_fetcher: None,
         }
     }
     fn from_str(s: &'a str) -> Self {
-        Self::default_with_source(Source::Str(s))
+        Self::default_with_source(Source::Str(Cow::Borrowed(s)))
     }
     fn from_file<P: AsRef<Path>>(path: P) -> Self {
         Self::default_with_source(Source::File(path.as_ref().to_owned()))
@@ -131,6 +178,14 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: ManualAnnot(ty),
             source: self.source,
             allow_imports: self.allow_imports,
+            import_policy: self.import_policy,
+            offline: self.offline,
+            use_cache: self.use_cache,
+            cache_dir: self.cache_dir,
+            read_only_cache: self.read_only_cache,
+            semi_semantic_cache: self.semi_semantic_cache,
+            standard_version: self.standard_version,
+            remote_fetcher: self.remote_fetcher,
         }
     }
 
@@ -181,6 +236,14 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: StaticAnnot,
             source: self.source,
             allow_imports: self.allow_imports,
+            import_policy: self.import_policy,
+            offline: self.offline,
+            use_cache: self.use_cache,
+            cache_dir: self.cache_dir,
+            read_only_cache: self.read_only_cache,
+            semi_semantic_cache: self.semi_semantic_cache,
+            standard_version: self.standard_version,
+            remote_fetcher: self.remote_fetcher,
         }
     }
 }
@@ -214,14 +277,261 @@ impl<'a, A> Deserializer<'a, A> {
         }
     }
 
-    // /// TODO
-    // pub fn remote_imports(&mut self, imports: bool) -> &mut Self {
-    //     self.allow_remote_imports = imports;
-    //     if imports {
-    //         self.allow_imports = true;
-    //     }
-    //     self
-    // }
+    /// Sets whether to enable remote imports.
+    ///
+    /// By default, remote imports are enabled. This is a shorthand for changing the
+    /// [`import_policy()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "https://example.com/config.dhall";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .remote_imports(false)
+    ///         .parse::<u64>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`import_policy()`]: Deserializer::import_policy()
+    pub fn remote_imports(self, imports: bool) -> Self {
+        let import_policy = self.import_policy.clone().remote(imports);
+        Deserializer {
+            import_policy,
+            ..self
+        }
+    }
+
+    /// Restricts which imports are allowed, e.g. to prevent reading some files or environment
+    /// variables.
+    ///
+    /// By default, all imports are allowed. This has no effect if imports are disabled with
+    /// [`imports()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::ImportPolicy;
+    ///
+    /// let policy = ImportPolicy::default()
+    ///     .remote(false)
+    ///     .local_root("./config")
+    ///     .allowed_env_vars(vec!["HOME"]);
+    /// let data = "env:AWS_SECRET_ACCESS_KEY as Text";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .import_policy(policy)
+    ///         .parse::<String>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`imports()`]: Deserializer::imports()
+    pub fn import_policy(self, import_policy: ImportPolicy) -> Self {
+        Deserializer {
+            import_policy,
+            ..self
+        }
+    }
+
+    /// Sets whether to forbid network access.
+    ///
+    /// In offline mode, remote imports must be protected by a hash and already be in the cache;
+    /// any other remote import is an error. Local and environment imports are unaffected. By
+    /// default, remote imports are downloaded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "https://example.com/config.dhall";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .offline(true)
+    ///         .parse::<u64>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn offline(self, offline: bool) -> Self {
+        Deserializer { offline, ..self }
+    }
+
+    /// Sets whether to use the on-disk cache for imports protected by a hash.
+    ///
+    /// By default, the cache is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "1 + 1";
+    /// let n: u64 = serde_dhall::from_str(data).use_cache(false).parse()?;
+    /// assert_eq!(n, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn use_cache(self, use_cache: bool) -> Self {
+        Deserializer { use_cache, ..self }
+    }
+
+    /// Sets the directory of the on-disk cache.
+    ///
+    /// By default, the cache is in `$XDG_CACHE_HOME/dhall`, or `$HOME/.cache/dhall`. The directory
+    /// is only created when an entry is written to it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "1 + 1";
+    /// let n: u64 = serde_dhall::from_str(data)
+    ///     .cache_dir("./target/dhall-cache")
+    ///     .parse()?;
+    /// assert_eq!(n, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cache_dir(self, dir: impl AsRef<Path>) -> Self {
+        Deserializer {
+            cache_dir: Some(dir.as_ref().to_owned()),
+            ..self
+        }
+    }
+
+    /// Sets whether the on-disk cache may only be read from.
+    ///
+    /// By default, imports protected by a hash are added to the cache after being fetched.
+    pub fn read_only_cache(self, read_only: bool) -> Self {
+        Deserializer {
+            read_only_cache: read_only,
+            ..self
+        }
+    }
+
+    /// Sets whether to also cache imports that aren't protected by a hash.
+    ///
+    /// Such imports are cached using the hash of their source and of the values of their own
+    /// imports, so they only need to be typechecked and normalized again when one of these
+    /// changes. They are stored in the `semi-semantic` subdirectory of the cache. By default, only
+    /// imports protected by a hash are cached.
+    pub fn semi_semantic_cache(self, enabled: bool) -> Self {
+        Deserializer {
+            semi_semantic_cache: enabled,
+            ..self
+        }
+    }
+
+    /// Rejects Dhall code, including imported files, that uses features added after `version`
+    /// of the standard. This keeps files compatible with other implementations that only support
+    /// that version. By default, every supported feature is allowed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::StandardVersion;
+    ///
+    /// let data = r#"Text/replace "a" "b" "aa""#;
+    /// let version = StandardVersion::new(18, 0, 0);
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .standard_version(version)
+    ///         .parse::<String>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn standard_version(self, version: StandardVersion) -> Self {
+        Deserializer {
+            standard_version: Some(version),
+            ..self
+        }
+    }
+
+    /// Downloads remote files with `fetch_remote`, given the url and the headers to send, instead
+    /// of accessing the network.
+    ///
+    /// This is how remote imports work on wasm, where this crate can't access the network: the
+    /// embedder provides a function that fetches the file, e.g. with a synchronous request in the
+    /// browser. Local and environment imports are unaffected.
+    ///
+    /// The function must be `Send` and `Sync`, except on wasm.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "https://example.com/list.dhall";
+    /// let list: Vec<u64> = serde_dhall::from_str(data)
+    ///     .remote_fetcher(|url, _headers| {
+    ///         assert_eq!(url.as_str(), "https://example.com/list.dhall");
+    ///         Ok("[1, 2]".to_owned().into())
+    ///     })
+    ///     .parse()?;
+    /// assert_eq!(list, vec![1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remote_fetcher(
+        self,
+        fetch_remote: impl Fn(&Url, &[(String, String)]) -> dhall::error::Result<RemoteFile>
+            + MaybeSendSync
+            + 'static,
+    ) -> Self {
+        Deserializer {
+            remote_fetcher: Some(RemoteFetcher(Arc::new(fetch_remote))),
+            ..self
+        }
+    }
+
+    fn parse_source(&self) -> dhall::error::Result<Parsed> {
+        match &self.source {
+            Source::Str(s) => Parsed::parse_str(s),
+            Source::File(p) => Parsed::parse_file(p.as_ref()),
+            Source::BinaryFile(p) => Parsed::parse_binary_file(p.as_ref()),
+        }
+    }
+
+    fn make_import_env<'cx>(&self, cx: Ctxt<'cx>) -> ImportEnv<'cx> {
+        let env = ImportEnv::new(cx)
+            .with_policy(self.import_policy.clone())
+            .offline(self.offline)
+            .semi_semantic_cache(self.semi_semantic_cache);
+        let env = match self.standard_version {
+            Some(version) => env.with_standard_version(version),
+            None => env,
+        };
+        let env = match &self.remote_fetcher {
+            Some(RemoteFetcher(fetch_remote)) => {
+                let fetch_remote = fetch_remote.clone();
+                env.with_fetcher(FnFetcher::new(
+                    move |url: &Url, headers: &[_]| fetch_remote(url, headers),
+                ))
+            }
+            None => env,
+        };
+        let cache = match &self.cache_dir {
+            _ if !self.use_cache => None,
+            Some(dir) => Some(Cache::at(dir)),
+            None => Cache::new().ok(),
+        };
+        match cache {
+            Some(cache) => {
+                env.with_cache(cache.read_only(self.read_only_cache))
+            }
+            None => env.without_cache(),
+        }
+    }
 
     fn _parse<T>(&self) -> dhall::error::Result<Result<Value>>
     where
@@ -229,28 +539,43 @@ impl<'a, A> Deserializer<'a, A> {
         T: HasAnnot<A>,
     {
         Ctxt::with_new(|cx| {
-            let parsed = match &self.source {
-                Source::Str(s) => Parsed::parse_str(s)?,
-                Source::File(p) => Parsed::parse_file(p.as_ref())?,
-                Source::BinaryFile(p) => Parsed::parse_binary_file(p.as_ref())?,
-            };
+            let parsed = self.parse_source()?;
             let resolved = if self.allow_imports {
-                parsed.resolve(cx)?
+                parsed.resolve_with_env(&mut self.make_import_env(cx))?
             } else {
+                if let Some(version) = self.standard_version {
+                    parsed.check_standard_version(version)?;
+                }
                 parsed.skip_resolve(cx)?
             };
-            let typed = match &T::get_annot(self.annot) {
-                None => resolved.typecheck(cx)?,
-                Some(ty) => resolved.typecheck_with(cx, &ty.to_hir())?,
-            };
-            Ok(Value::from_nir_and_ty(
-                cx,
-                typed.normalize(cx).as_nir(),
-                typed.ty().as_nir(),
-            ))
+            to_value(cx, resolved, T::get_annot(self.annot))
         })
     }
 
+    /// A copy of the options, without the type annotation and owning the source, that can be
+    /// moved to another thread.
+    #[cfg(feature = "async")]
+    fn to_owned_untyped(&self) -> Deserializer<'static, NoAnnot> {
+        let source = match &self.source {
+            Source::Str(s) => Source::Str(Cow::Owned(s.to_string())),
+            Source::File(p) => Source::File(p.clone()),
+            Source::BinaryFile(p) => Source::BinaryFile(p.clone()),
+        };
+        Deserializer {
+            annot: NoAnnot,
+            source,
+            allow_imports: self.allow_imports,
+            import_policy: self.import_policy.clone(),
+            offline: self.offline,
+            use_cache: self.use_cache,
+            cache_dir: self.cache_dir.clone(),
+            read_only_cache: self.read_only_cache,
+            semi_semantic_cache: self.semi_semantic_cache,
+            standard_version: self.standard_version,
+            remote_fetcher: self.remote_fetcher.clone(),
+        }
+    }
+
     /// Parses the chosen dhall value with the options provided.
     ///
     /// If you enabled static annotations, `T` is required to implement [`StaticType`].
@@ -278,6 +603,78 @@ impl<'a, A> Deserializer<'a, A> {
             .map_err(Error)??;
         T::from_dhall(&val)
     }
+
+    /// Like [`parse()`], but without blocking the async runtime. Use this inside a tokio runtime,
+    /// where [`parse()`] could panic. Remote imports are downloaded without blocking, and
+    /// everything else runs on tokio's blocking thread pool.
+    ///
+    /// The returned future is `Send`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let runtime = tokio::runtime::Builder::new_current_thread()
+    ///     .enable_all()
+    ///     .build()
+    ///     .unwrap();
+    /// let data = runtime.block_on(async {
+    ///     serde_dhall::from_str("6 * 7").parse_async::<u64>().await
+    /// })?;
+    /// assert_eq!(data, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`parse()`]: Deserializer::parse()
+    #[cfg(feature = "async")]
+    pub async fn parse_async<T>(&self) -> Result<T>
+    where
+        A: TypeAnnot,
+        T: FromDhall + HasAnnot<A>,
+    {
+        let options = Arc::new(self.to_owned_untyped());
+        let annot = T::get_annot(self.annot);
+        let parse = {
+            let options = options.clone();
+            move || {
+                let parsed = options.parse_source()?;
+                if options.allow_imports {
+                    Ok(parsed)
+                } else {
+                    // Like `skip_resolve`.
+                    Ok(Parsed::from_expr_without_imports(parsed.to_expr()))
+                }
+            }
+        };
+        let val = Parsed::resolve_async(
+            parse,
+            move |cx| options.make_import_env(cx),
+            move |cx, resolved| to_value(cx, resolved, annot),
+        )
+        .await
+        .and_then(|val| val)
+        .map_err(ErrorKind::Dhall)
+        .map_err(Error)??;
+        T::from_dhall(&val)
+    }
+}
+
+/// Typecheck `resolved` against `annot`, and convert it to a `Value`.
+fn to_value<'cx>(
+    cx: Ctxt<'cx>,
+    resolved: Resolved<'cx>,
+    annot: Option<SimpleType>,
+) -> dhall::error::Result<Result<Value>> {
+    let typed = match &annot {
+        None => resolved.typecheck(cx)?,
+        Some(ty) => resolved.typecheck_with(cx, &ty.to_hir())?,
+    };
+    Ok(Value::from_nir_and_ty(
+        cx,
+        typed.normalize_checked(cx)?.as_nir(),
+        typed.ty().as_nir(),
+    ))
 }
 
 /// Deserialize a value from a string of Dhall text.
@@ -113,11 +113,8 @@ impl ser::Serializer for Serializer {
         Ok(Text(v.to_owned()))
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: byte array".to_owned(),
-        )
-        .into())
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(Num(NumKind::Bytes(v.to_owned())))
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
@@ -356,6 +353,10 @@ impl serde::ser::Serialize for SimpleValue {
             Num(Natural(x)) => serializer.serialize_u64(*x),
             Num(Integer(x)) => serializer.serialize_i64(*x),
             Num(Double(x)) => serializer.serialize_f64((*x).into()),
+            Num(Date(x)) => serializer.serialize_str(&x.to_string()),
+            Num(Time(x)) => serializer.serialize_str(&x.to_string()),
+            Num(TimeZone(x)) => serializer.serialize_str(&x.to_string()),
+            Num(Bytes(x)) => serializer.serialize_bytes(x),
             Text(x) => serializer.serialize_str(x),
             List(xs) => {
                 let mut seq = serializer.serialize_seq(Some(xs.len()))?;
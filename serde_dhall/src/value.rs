@@ -1,9 +1,10 @@
 use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 
 use dhall::builtins::Builtin;
 use dhall::operations::OpKind;
 use dhall::semantics::{Hir, HirKind, Nir, NirKind};
-pub use dhall::syntax::NumKind;
+pub use dhall::syntax::{DateLit, NumKind, TimeLit, TimeZoneLit};
 use dhall::syntax::{Expr, ExprKind, Span};
 use dhall::Ctxt;
 
@@ -98,7 +99,8 @@ pub struct Value {
 /// [`from_simple_value()`]: crate::from_simple_value()
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SimpleValue {
-    /// Numbers and booleans - `True`, `1`, `+2`, `3.24`
+    /// Numbers, booleans, dates, times and bytes - `True`, `1`, `+2`, `3.24`, `2021-01-31`,
+    /// `12:00:00`, `+01:00`, `0x"00ff"`
     Num(NumKind),
     /// A string of text - `"Hello world!"`
     Text(String),
@@ -138,6 +140,10 @@ pub enum SimpleValue {
 /// `Integer`  | `i64`, `i32`, ...
 /// `Double`  | `f64`, `f32`, ...
 /// `Text`  | `String`
+/// `Date`  | `String`, e.g. `"2021-01-31"`
+/// `Time`  | `String`, e.g. `"12:00:00"`
+/// `TimeZone`  | `String`, e.g. `"+01:00"`
+/// `Bytes`  | `Vec<u8>`, `serde_bytes::ByteBuf`
 /// `List T`  | `Vec<T>`
 /// `Optional T`  | `Option<T>`
 /// `{ x: T, y: U }`  | structs
@@ -149,6 +155,11 @@ pub enum SimpleValue {
 /// `Prelude.JSON.Type`  | unsupported
 /// `Prelude.Map.Type T U`  | unsupported
 ///
+/// A plain `Vec<u8>` can be read from `Bytes`, but serde serializes it as a list, so it is only
+/// written as `Bytes` when the type annotation says so. Use
+/// [`serde_bytes`](https://docs.rs/serde_bytes) to always write `Bytes`. `u8` has no
+/// [`StaticType`], so types containing bytes need a hand-written `StaticType` impl.
+///
 /// # Examples
 ///
 /// ```rust
@@ -196,6 +207,14 @@ pub enum SimpleType {
     Double,
     /// Corresponds to the Dhall type `Text`
     Text,
+    /// Corresponds to the Dhall type `Date`
+    Date,
+    /// Corresponds to the Dhall type `Time`
+    Time,
+    /// Corresponds to the Dhall type `TimeZone`
+    TimeZone,
+    /// Corresponds to the Dhall type `Bytes`
+    Bytes,
     /// Corresponds to the Dhall type `Optional T`
     Optional(Box<SimpleType>),
     /// Corresponds to the Dhall type `List T`
@@ -362,10 +381,35 @@ impl SimpleValue {
             | (V::Num(num @ NumKind::Natural(_)), Some(T::Natural))
             | (V::Num(num @ NumKind::Integer(_)), Some(T::Integer))
             | (V::Num(num @ NumKind::Double(_)), Some(T::Double))
+            | (V::Num(num @ NumKind::Date(_)), Some(T::Date))
+            | (V::Num(num @ NumKind::Time(_)), Some(T::Time))
+            | (V::Num(num @ NumKind::TimeZone(_)), Some(T::TimeZone))
+            | (V::Num(num @ NumKind::Bytes(_)), Some(T::Bytes))
             | (V::Num(num), None) => ExprKind::Num(num.clone()),
             (V::Text(v), Some(T::Text)) | (V::Text(v), None) => {
                 ExprKind::TextLit(v.clone().into())
             }
+            // Dates and times are serialized as strings.
+            (V::Text(v), Some(T::Date)) => ExprKind::Num(NumKind::Date(
+                v.parse().map_err(|_| type_error())?,
+            )),
+            (V::Text(v), Some(T::Time)) => ExprKind::Num(NumKind::Time(
+                v.parse().map_err(|_| type_error())?,
+            )),
+            (V::Text(v), Some(T::TimeZone)) => ExprKind::Num(
+                NumKind::TimeZone(v.parse().map_err(|_| type_error())?),
+            ),
+            // A `Vec<u8>` is serialized as a list of numbers.
+            (V::List(v), Some(T::Bytes)) => ExprKind::Num(NumKind::Bytes(
+                v.iter()
+                    .map(|x| match x {
+                        V::Num(NumKind::Natural(n)) => {
+                            u8::try_from(*n).map_err(|_| type_error())
+                        }
+                        _ => Err(type_error()),
+                    })
+                    .collect::<Result<_>>()?,
+            )),
 
             (V::Optional(None), None) => return Err(type_missing()),
             (V::Optional(None), Some(T::Optional(t))) => {
@@ -459,6 +503,10 @@ impl SimpleType {
                 Builtin::Integer => SimpleType::Integer,
                 Builtin::Double => SimpleType::Double,
                 Builtin::Text => SimpleType::Text,
+                Builtin::Date => SimpleType::Date,
+                Builtin::Time => SimpleType::Time,
+                Builtin::TimeZone => SimpleType::TimeZone,
+                Builtin::Bytes => SimpleType::Bytes,
                 _ => unreachable!(),
             },
             NirKind::OptionalType(t) => {
@@ -477,9 +525,10 @@ impl SimpleType {
                     .map(|(k, v)| {
                         Some((
                             k.into(),
-                            v.as_ref()
-                                .map(|v| Ok(Self::from_nir(v)?))
-                                .transpose()?,
+                            match v {
+                                Some(v) => Some(Self::from_nir(v)?),
+                                None => None,
+                            },
                         ))
                     })
                     .collect::<Option<_>>()?,
@@ -496,6 +545,10 @@ impl SimpleType {
             SimpleType::Integer => ExprKind::Builtin(Builtin::Integer),
             SimpleType::Double => ExprKind::Builtin(Builtin::Double),
             SimpleType::Text => ExprKind::Builtin(Builtin::Text),
+            SimpleType::Date => ExprKind::Builtin(Builtin::Date),
+            SimpleType::Time => ExprKind::Builtin(Builtin::Time),
+            SimpleType::TimeZone => ExprKind::Builtin(Builtin::TimeZone),
+            SimpleType::Bytes => ExprKind::Builtin(Builtin::Bytes),
             SimpleType::Optional(t) => ExprKind::Op(OpKind::App(
                 hir(ExprKind::Builtin(Builtin::Optional)),
                 t.to_hir(),
@@ -55,6 +55,15 @@ mod serde {
 
         assert_serde("1.0", 1.0f64);
         assert_serde("1.0", 1.0f32);
+
+        assert_eq!(
+            from_str("Natural/toInteger (18446744073709551615 + 0)")
+                .static_type_annotation()
+                .parse::<i64>()
+                .map_err(|e| e.to_string()),
+            Err("`Natural/toInteger 18446744073709551615` overflows: `Natural` and `Integer` are limited to 64 bits".to_string())
+        );
+        assert!(from_str("18446744073709551616").parse::<u64>().is_err());
     }
 
     #[test]
@@ -149,6 +158,130 @@ mod serde {
             .is_err());
     }
 
+    #[test]
+    fn temporal() {
+        use serde_dhall::SimpleType;
+
+        let de = |s: &str, ty| {
+            from_str(s)
+                .type_annotation(ty)
+                .parse::<String>()
+                .map_err(|e| e.to_string())
+        };
+        let ser = |x: &str, ty| {
+            serialize(&x)
+                .type_annotation(ty)
+                .to_string()
+                .map_err(|e| e.to_string())
+        };
+        for (s, ty) in &[
+            ("2021-01-31", SimpleType::Date),
+            ("12:00:00.50", SimpleType::Time),
+            ("-01:00", SimpleType::TimeZone),
+        ] {
+            assert_eq!(de(s, ty), Ok(s.to_string()));
+            assert_eq!(ser(s, ty), Ok(s.to_string()));
+        }
+        assert!(ser("2021-02-30", &SimpleType::Date).is_err());
+        assert!(ser("12:00", &SimpleType::Time).is_err());
+
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct Timestamp {
+            date: String,
+            time: String,
+            #[serde(rename = "timeZone")]
+            time_zone: String,
+        }
+        assert_eq!(
+            from_str("2024-01-31T12:00:00Z")
+                .parse::<Timestamp>()
+                .unwrap(),
+            Timestamp {
+                date: "2024-01-31".to_owned(),
+                time: "12:00:00".to_owned(),
+                time_zone: "+00:00".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        use serde_dhall::SimpleType;
+
+        assert_eq!(
+            from_str(r#"0x"00ff""#).parse::<Vec<u8>>().unwrap(),
+            vec![0, 255]
+        );
+        assert_eq!(
+            from_str(r#"0x"00ff""#)
+                .type_annotation(&SimpleType::Bytes)
+                .parse::<Vec<u8>>()
+                .unwrap(),
+            vec![0, 255]
+        );
+        let ser = |x: &[u64]| {
+            serialize(&x)
+                .type_annotation(&SimpleType::Bytes)
+                .to_string()
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(ser(&[0, 255]), Ok(r#"0x"00FF""#.to_string()));
+        assert_eq!(ser(&[]), Ok(r#"0x"""#.to_string()));
+        assert!(ser(&[256]).is_err());
+
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        struct Key {
+            #[serde(with = "serde_bytes")]
+            der: Vec<u8>,
+        }
+        impl StaticType for Key {
+            fn static_type() -> SimpleType {
+                SimpleType::Record(
+                    vec![("der".to_owned(), SimpleType::Bytes)]
+                        .into_iter()
+                        .collect(),
+                )
+            }
+        }
+        let key = Key {
+            der: vec![0x30, 0x82],
+        };
+        let dhall = serialize(&key).to_string().unwrap();
+        assert_eq!(dhall, r#"{ der = 0x"3082" }"#);
+        assert_eq!(from_str(&dhall).parse::<Key>().unwrap(), key);
+        let dhall = serialize(&key)
+            .static_type_annotation()
+            .to_string()
+            .unwrap();
+        assert_eq!(dhall, r#"{ der = 0x"3082" }"#);
+        assert_eq!(
+            from_str(&dhall)
+                .static_type_annotation()
+                .parse::<Key>()
+                .unwrap(),
+            key
+        );
+        assert!(from_str("{ der = [48, 130] }")
+            .static_type_annotation()
+            .parse::<Key>()
+            .is_err());
+
+        let buf = serde_bytes::ByteBuf::from(vec![0, 255]);
+        let dhall = serialize(&buf).to_string().unwrap();
+        assert_eq!(dhall, r#"0x"00FF""#);
+        assert_eq!(
+            from_str(&dhall).parse::<serde_bytes::ByteBuf>().unwrap(),
+            buf
+        );
+        assert_eq!(
+            from_str(&dhall)
+                .type_annotation(&SimpleType::Bytes)
+                .parse::<serde_bytes::ByteBuf>()
+                .unwrap(),
+            buf
+        );
+    }
+
     #[test]
     fn test_de_untyped() {
         use std::collections::BTreeMap;
@@ -247,6 +380,36 @@ mod serde {
         );
     }
 
+    #[test]
+    #[cfg(feature = "async")]
+    fn parse_async() {
+        fn assert_send<T: Send>(x: T) -> T {
+            x
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let dir = std::env::temp_dir()
+            .join(format!("serde-dhall-parse-async-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.dhall"), "./b.dhall + 1").unwrap();
+        std::fs::write(dir.join("b.dhall"), "41").unwrap();
+
+        let de = serde_dhall::from_file(dir.join("a.dhall"))
+            .static_type_annotation();
+        let res = runtime.block_on(assert_send(de.parse_async::<u64>()));
+        assert_eq!(res.map_err(|e| e.to_string()), Ok(42));
+        let de = from_str("1 + 1").imports(false);
+        let res = runtime.block_on(de.parse_async::<u64>());
+        assert_eq!(res.map_err(|e| e.to_string()), Ok(2));
+        let source = format!("{}", dir.join("b.dhall").display());
+        let de = from_str(&source).imports(false);
+        assert!(runtime.block_on(de.parse_async::<u64>()).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     #[ignore] // Way too slow
     fn test_prelude() {
@@ -1,7 +1,8 @@
 mod simple_value {
     use serde::{Deserialize, Serialize};
     use serde_dhall::{
-        from_str, serialize, FromDhall, NumKind, SimpleValue, ToDhall, Value,
+        from_str, serialize, DateLit, FromDhall, NumKind, SimpleType,
+        SimpleValue, ToDhall, Value,
     };
 
     fn assert_de<T>(s: &str, x: T)
@@ -55,6 +56,30 @@ mod simple_value {
             },
         );
 
+        assert_serde(
+            r#"0x"00FF""#,
+            SimpleValue::Num(NumKind::Bytes(vec![0, 255])),
+        );
+
+        let date = SimpleValue::Num(NumKind::Date(DateLit {
+            year: 2021,
+            month: 1,
+            day: 31,
+        }));
+        // Dates go through serde as strings.
+        assert_eq!(
+            serde_dhall::from_simple_value::<String>(date.clone())
+                .map_err(|e| e.to_string()),
+            Ok("2021-01-31".to_string())
+        );
+        assert_eq!(
+            serialize(&date)
+                .type_annotation(&SimpleType::Date)
+                .to_string()
+                .map_err(|e| e.to_string()),
+            Ok("2021-01-31".to_string())
+        );
+
         // Neither a simple value or a simple type.
         let not_simple = "Type → Type";
         assert_eq!(
@@ -10,3 +10,28 @@ fn test() {
     }
     assert_eq!(parse::<Vec<u64>>("[1, 2]"), vec![1, 2]);
 }
+
+#[wasm_bindgen_test]
+fn remote_fetcher() {
+    let list: Vec<u64> = from_str("https://example.com/list.dhall")
+        .remote_fetcher(|url, _headers| {
+            assert_eq!(url.as_str(), "https://example.com/list.dhall");
+            Ok("[1, 2]".to_owned().into())
+        })
+        .static_type_annotation()
+        .parse()
+        .unwrap();
+    assert_eq!(list, vec![1, 2]);
+}
+
+#[wasm_bindgen_test]
+fn non_send_remote_fetcher() {
+    // Like a `js_sys::Function`, an `Rc` is neither `Send` nor `Sync`.
+    let body = std::rc::Rc::new("[1, 2]".to_owned());
+    let list: Vec<u64> = from_str("https://example.com/list.dhall")
+        .remote_fetcher(move |_url, _headers| Ok(body.to_string().into()))
+        .static_type_annotation()
+        .parse()
+        .unwrap();
+    assert_eq!(list, vec![1, 2]);
+}